@@ -4,12 +4,42 @@ use std::{
     sync::{Arc, atomic::AtomicBool},
 };
 use termcolor::BufferWriter;
-use tui_video_chat::{feed::frame::AsciiEncoding, webcam::WebCam, window::Window};
+use tui_video_chat::{
+    capabilities::Capabilities,
+    config::take_flag,
+    feed::frame::AsciiEncoding,
+    registry::{SourceRegistry, SourceRequest, SourceSpec},
+    render::Renderer,
+    window::Window,
+};
 
 const ENCODING: [char; 8] = [':', '-', '=', '+', '*', '%', '@', '#'];
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let registry = SourceRegistry::default();
+
+    let mut args = std::env::args().skip(1).collect::<Vec<String>>();
+    if take_flag(&mut args, "--list-sources") {
+        registry
+            .describe()
+            .iter()
+            .for_each(|line| println!("{line}"));
+        return Ok(());
+    }
+
+    let source = SourceSpec::take_from_args(&mut args)?.unwrap_or_default();
+    let renderer = Renderer::take_from_args(
+        &mut args,
+        AsciiEncoding(ENCODING.to_vec()),
+        &Capabilities::detect(),
+    )?;
+
+    let (config, remaining) = (registry.get(&source.name)?.default_config)().parse_args(args)?;
+    if let Some(arg) = remaining.first() {
+        return Err(format!("Unknown argument `{arg}`.").into());
+    }
+
     let end_flag = Arc::new(AtomicBool::new(false));
     end_flag.load(std::sync::atomic::Ordering::SeqCst);
 
@@ -18,13 +48,27 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
         end_flag_ctrlc.store(true, std::sync::atomic::Ordering::SeqCst);
     })?;
 
+    let feed_source = registry.create(
+        &source.name,
+        &SourceRequest {
+            argument: source.argument.as_deref(),
+            config: &config,
+            end_flag: &end_flag,
+        },
+    )?;
+
     let window = Window::new(BufferWriter::alternate_stdout)?;
-    let encoding = AsciiEncoding(ENCODING.to_vec());
 
-    window.show_feed::<WebCam>(encoding, end_flag).await?;
+    let stats = renderer.stats.clone();
+
+    window
+        .show_feed_from(feed_source, renderer, config, end_flag)
+        .await?;
 
     print!("{}", termion::clear::All);
     stdout().flush()?;
 
+    eprintln!("{stats}");
+
     Ok(())
 }
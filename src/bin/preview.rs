@@ -0,0 +1,69 @@
+use std::{
+    error::Error,
+    io::{Write, stdout},
+    sync::{Arc, atomic::AtomicBool},
+};
+use termcolor::BufferWriter;
+use tui_video_chat::{
+    capabilities::Capabilities,
+    feed::frame::AsciiEncoding,
+    registry::{SourceRegistry, SourceRequest},
+    render::Renderer,
+    window::Window,
+};
+
+const ENCODING: [char; 8] = [':', '-', '=', '+', '*', '%', '@', '#'];
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let registry = SourceRegistry::default();
+
+    let mut args = std::env::args().skip(1).collect::<Vec<String>>();
+    let renderer = Renderer::take_from_args(
+        &mut args,
+        AsciiEncoding(ENCODING.to_vec()),
+        &Capabilities::detect(),
+    )?;
+
+    let (config, remaining) = (registry.get("file")?.default_config)().parse_args(args)?;
+    let path = match &remaining[..] {
+        [path] => path.clone(),
+        _ => {
+            return Err(
+                "Usage: preview [--render MODE] [--colors DEPTH] [--dither DITHER] [--scale SCALING] [--cell-size WxH] [--config PATH] [--<option> VALUE]... <image, gif or y4m file>"
+                    .into(),
+            );
+        }
+    };
+
+    let end_flag = Arc::new(AtomicBool::new(false));
+
+    let end_flag_ctrlc = end_flag.clone();
+    ctrlc::set_handler(move || {
+        end_flag_ctrlc.store(true, std::sync::atomic::Ordering::SeqCst);
+    })?;
+
+    let feed_source = registry.create(
+        "file",
+        &SourceRequest {
+            argument: Some(&path),
+            config: &config,
+            end_flag: &end_flag,
+        },
+    )?;
+
+    let window = Window::new(BufferWriter::alternate_stdout)?;
+
+    let stats = renderer.stats.clone();
+
+    window
+        .show_feed_from(feed_source, renderer, config, end_flag)
+        .await?;
+
+    print!("{}", termion::clear::All);
+    stdout().flush()?;
+
+    eprintln!("{stats}");
+
+    Ok(())
+}
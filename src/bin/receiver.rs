@@ -4,12 +4,31 @@ use std::{
     sync::{Arc, atomic::AtomicBool},
 };
 use termcolor::BufferWriter;
-use tui_video_chat::{feed::frame::AsciiEncoding, stream::connect, webcam::WebCam, window::Window};
+use tui_video_chat::{
+    capabilities::Capabilities,
+    feed::{Feed, frame::AsciiEncoding},
+    render::Renderer,
+    stream::connect,
+    webcam::WebCam,
+    window::Window,
+};
 
 const ENCODING: [char; 8] = [':', '-', '=', '+', '*', '%', '@', '#'];
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut args = std::env::args().skip(1).collect::<Vec<String>>();
+    let renderer = Renderer::take_from_args(
+        &mut args,
+        AsciiEncoding(ENCODING.to_vec()),
+        &Capabilities::detect(),
+    )?;
+
+    let (config, remaining) = WebCam::default_config().parse_args(args)?;
+    if let Some(arg) = remaining.first() {
+        return Err(format!("Unknown argument `{arg}`.").into());
+    }
+
     let end_flag = Arc::new(AtomicBool::new(false));
     end_flag.load(std::sync::atomic::Ordering::SeqCst);
 
@@ -19,16 +38,19 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     })?;
 
     let window = Window::new(BufferWriter::alternate_stdout)?;
-    let encoding = AsciiEncoding(ENCODING.to_vec());
 
     let connection = connect(3000, "localhost:3001").await?;
 
+    let stats = renderer.stats.clone();
+
     window
-        .show_stream_feed::<WebCam>(connection, encoding, end_flag)
+        .show_stream_feed::<WebCam>(connection, renderer, config, end_flag)
         .await?;
 
     print!("{}", termion::clear::All);
     stdout().flush()?;
 
+    eprintln!("{stats}");
+
     Ok(())
 }
@@ -1,13 +1,42 @@
 use std::{
     error::Error,
-    io::{Write, stdout},
+    io::{Write, stdin, stdout},
     sync::{Arc, atomic::AtomicBool},
 };
 use termcolor::BufferWriter;
-use tui_video_chat::{stream::connect, webcam::WebCam, window::Window};
+use termion::{event::Key, input::TermRead, raw::IntoRawMode};
+use tui_video_chat::{
+    config::{FeedConfig, parse_geometry, take_flag},
+    feed::FeedSource,
+    raw_input::RawInput,
+    registry::{SourceRegistry, SourceRequest, SourceSpec},
+    stream::connect,
+    switch::SourceSwitch,
+    window::Window,
+};
+
+const USAGE: &str = "Usage: sender [--list-sources] [--config PATH] [--<option> VALUE]... [--source NAME[:ARGUMENT] | --stdin WIDTHxHEIGHT | --pipe PATH WIDTHxHEIGHT]";
+
+/// Keys that switch the streamed source to the registry source with the same name while streaming.
+const SHORTCUTS: [(char, &str); 3] = [('w', "webcam"), ('s', "screen"), ('t', "test-pattern")];
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let registry = SourceRegistry::default();
+
+    let mut args = std::env::args().skip(1).collect::<Vec<String>>();
+    if take_flag(&mut args, "--list-sources") {
+        registry
+            .describe()
+            .iter()
+            .for_each(|line| println!("{line}"));
+        return Ok(());
+    }
+
+    let source = SourceSpec::take_from_args(&mut args)?.unwrap_or_default();
+
+    let (config, args) = (registry.get(&source.name)?.default_config)().parse_args(args)?;
+
     let end_flag = Arc::new(AtomicBool::new(false));
     end_flag.load(std::sync::atomic::Ordering::SeqCst);
 
@@ -16,14 +45,120 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
         end_flag_ctrlc.store(true, std::sync::atomic::Ordering::SeqCst);
     })?;
 
+    let switch = SourceSwitch::new();
+
+    // Stdin can only be read for shortcuts when it isn't carrying the frames.
+    let (feed_source, active, listen_shortcuts): (Box<dyn FeedSource>, Option<SourceSpec>, bool) =
+        match args.iter().map(String::as_str).collect::<Vec<&str>>()[..] {
+            [] => {
+                let feed_source = registry.create(
+                    &source.name,
+                    &SourceRequest {
+                        argument: source.argument.as_deref(),
+                        config: &config,
+                        end_flag: &end_flag,
+                    },
+                )?;
+                (feed_source, Some(source.clone()), source.name != "stdin")
+            }
+            ["--stdin", geometry] => {
+                let (width, height) = parse_geometry(geometry)?;
+                let raw_input = RawInput::stdin(width, height, end_flag.clone())?;
+                (Box::new(raw_input), None, false)
+            }
+            ["--pipe", path, geometry] => {
+                let (width, height) = parse_geometry(geometry)?;
+                let raw_input = RawInput::fifo(path, width, height, end_flag.clone())?;
+                (Box::new(raw_input), None, true)
+            }
+            _ => return Err(USAGE.into()),
+        };
+
     let window = Window::new(BufferWriter::alternate_stdout)?;
 
+    let _raw_terminal = match listen_shortcuts && termion::is_tty(&stdin()) {
+        true => {
+            let raw_terminal = stdout().into_raw_mode()?;
+            let shortcuts = SHORTCUTS
+                .iter()
+                .map(|&(key, name)| match name == source.name {
+                    true => (key, source.clone()),
+                    false => (
+                        key,
+                        SourceSpec {
+                            name: name.to_string(),
+                            argument: None,
+                        },
+                    ),
+                })
+                .collect();
+            listen_for_shortcuts(
+                registry,
+                shortcuts,
+                active,
+                switch.clone(),
+                config,
+                end_flag.clone(),
+            );
+            Some(raw_terminal)
+        }
+        false => None,
+    };
+
     let connection = connect(3001, "localhost:3000").await?;
 
-    window.stream_feed::<WebCam>(connection, end_flag).await?;
+    window
+        .stream_feed_switchable(feed_source, switch, connection, config, end_flag)
+        .await?;
 
     print!("{}", termion::clear::All);
     stdout().flush()?;
 
     Ok(())
 }
+
+/// Function that reads the keyboard on a separate thread and swaps the streamed source when a shortcut is pressed.
+/// Each shortcut opens the source as it was given on the command line when it is the starting one, and the source
+/// already streaming isn't opened again. The new source is opened on this thread so the stream keeps sending the old
+/// one until it is ready. With the terminal in raw mode ctrl-c arrives as a key, so it also ends the stream like `q`.
+fn listen_for_shortcuts(
+    registry: SourceRegistry,
+    shortcuts: Vec<(char, SourceSpec)>,
+    mut active: Option<SourceSpec>,
+    switch: SourceSwitch<Box<dyn FeedSource>>,
+    config: FeedConfig,
+    end_flag: Arc<AtomicBool>,
+) {
+    std::thread::spawn(move || {
+        for key in stdin().keys() {
+            let spec = match key {
+                Ok(Key::Char('q')) | Ok(Key::Ctrl('c')) => {
+                    end_flag.store(true, std::sync::atomic::Ordering::SeqCst);
+                    break;
+                }
+                Err(_) => break,
+                Ok(Key::Char(c)) => match shortcuts.iter().find(|(shortcut, _)| *shortcut == c) {
+                    Some((_, spec)) if active.as_ref() != Some(spec) => spec,
+                    _ => continue,
+                },
+                _ => continue,
+            };
+
+            let request = SourceRequest {
+                argument: spec.argument.as_deref(),
+                config: &config,
+                end_flag: &end_flag,
+            };
+
+            // A source that fails to open (e.g. no camera) leaves the current one streaming. The terminal is in raw
+            // mode, so the line is ended by hand.
+            match registry.create(&spec.name, &request) {
+                Ok(feed_source) => {
+                    switch.switch_to(feed_source);
+                    active = Some(spec.clone());
+                }
+                Err(error) => eprint!("Couldn't switch to `{spec}`: {error}\r\n"),
+            }
+        }
+    });
+}
@@ -0,0 +1,532 @@
+//! Module that works out what the terminal can do (colors, image protocols, cell size...) to pick how frames are
+//! drawn.
+
+use crate::feed::frame::AsciiEncoding;
+use crate::render::RenderMode;
+use crate::render::color::ColorDepth;
+use crate::render::iterm::ItermFormat;
+use crate::render::kitty::KittyPayload;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::AsRawFd;
+use std::time::{Duration, Instant};
+use termion::raw::IntoRawMode;
+
+/// Time the terminal has to answer the queries before it is assumed not to support them.
+pub const QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Id of the image used to ask the terminal whether it supports the kitty graphics protocol.
+const KITTY_QUERY_ID: u32 = 31;
+
+/// DEC private mode of synchronized output.
+const SYNCHRONIZED_OUTPUT_MODE: u32 = 2026;
+
+/// Queries sent to the terminal: kitty graphics, synchronized output mode, cell size in pixels and, last, the
+/// primary device attributes. Every terminal answers the last one, so its reply marks the end of the replies.
+pub const QUERIES: &str = "\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\\x1b[?2026$p\x1b[16t\x1b[c";
+
+/// Enum that represents a terminal multiplexer the program runs inside of. Multiplexers sit between the program
+/// and the real terminal, so image protocols usually don't reach it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Multiplexer {
+    Tmux,
+    Screen,
+}
+
+/// Enum that represents a reply of the terminal to one of the queries.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reply {
+    /// Primary device attributes (`CSI ? Ps ; ... c`), attribute 4 means sixel support.
+    DeviceAttributes(Vec<u32>),
+    /// Answer to the kitty graphics query, `true` when the terminal accepted the image.
+    KittyGraphics(bool),
+    /// Report of a DEC private mode (`CSI ? mode ; value $ y`), value 1 to 3 means the mode is recognized.
+    Mode { mode: u32, value: u32 },
+    /// Size, in pixels, of a cell (`CSI 6 ; height ; width t`).
+    CellSize { width: u32, height: u32 },
+}
+
+/// Function that parses the numeric parameters of a control sequence.
+fn parse_parameters(parameters: &[u8]) -> Vec<u32> {
+    parameters
+        .split(|&byte| byte == b';')
+        .map(|parameter| {
+            std::str::from_utf8(parameter)
+                .ok()
+                .and_then(|parameter| parameter.parse().ok())
+                .unwrap_or(0)
+        })
+        .collect()
+}
+
+/// Function that finds the replies in the bytes read from the terminal, skipping anything it doesn't recognize
+/// (e.g. keys pressed while waiting). Replies cut at the end of the bytes are ignored.
+pub fn parse_replies(bytes: &[u8]) -> Vec<Reply> {
+    let mut replies = Vec::new();
+    let mut i = 0;
+
+    while i + 1 < bytes.len() {
+        if bytes[i] != 0x1b {
+            i += 1;
+            continue;
+        }
+
+        match bytes[i + 1] {
+            // Control sequence: parameters and intermediates up to a final byte in 0x40..=0x7e.
+            b'[' => {
+                let start = i + 2;
+                let end = match bytes[start..]
+                    .iter()
+                    .position(|b| (0x40..=0x7e).contains(b))
+                {
+                    Some(end) => start + end,
+                    None => break,
+                };
+                let body = &bytes[start..end];
+
+                let reply = match (body.first(), body.last(), bytes[end]) {
+                    (Some(b'?'), _, b'c') => {
+                        Some(Reply::DeviceAttributes(parse_parameters(&body[1..])))
+                    }
+                    (Some(b'?'), Some(b'$'), b'y') => {
+                        match parse_parameters(&body[1..body.len() - 1])[..] {
+                            [mode, value] => Some(Reply::Mode { mode, value }),
+                            _ => None,
+                        }
+                    }
+                    (_, _, b't') => match parse_parameters(body)[..] {
+                        [6, height, width] => Some(Reply::CellSize { width, height }),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+
+                replies.extend(reply);
+                i = end + 1;
+            }
+            // Application program command, ended by the string terminator.
+            b'_' => {
+                let start = i + 2;
+                let end = match bytes[start..].windows(2).position(|w| w == b"\x1b\\") {
+                    Some(end) => start + end,
+                    None => break,
+                };
+                let body = &bytes[start..end];
+
+                if let Some(body) = body.strip_prefix(b"G")
+                    && let Some(separator) = body.iter().position(|&b| b == b';')
+                {
+                    let (keys, message) = (&body[..separator], &body[separator + 1..]);
+                    if keys
+                        .split(|&b| b == b',')
+                        .any(|key| key == format!("i={KITTY_QUERY_ID}").as_bytes())
+                    {
+                        replies.push(Reply::KittyGraphics(message == b"OK"));
+                    }
+                }
+
+                i = end + 2;
+            }
+            _ => i += 1,
+        }
+    }
+
+    replies
+}
+
+/// Struct that represents what the terminal can do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Capabilities {
+    pub color_depth: ColorDepth,
+    pub sixel: bool,
+    pub kitty_graphics: bool,
+    /// Support for the iTerm2 inline image protocol, only known from the name of the terminal.
+    pub iterm_images: bool,
+    /// Size, in pixels, of a cell, when the terminal reports it.
+    pub cell_pixel_size: Option<(u32, u32)>,
+    /// Support for synchronized output (DEC private mode 2026).
+    pub synchronized_output: bool,
+    pub multiplexer: Option<Multiplexer>,
+}
+
+impl Capabilities {
+    /// Function that works out what the terminal can do from environment variables alone. `var` returns the value
+    /// of a variable, so any environment can be given.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let term = var("TERM");
+
+        let multiplexer = match (var("TMUX"), var("STY"), term.as_deref()) {
+            (Some(_), _, _) => Some(Multiplexer::Tmux),
+            (_, _, Some(term)) if term.starts_with("tmux") => Some(Multiplexer::Tmux),
+            (_, Some(_), _) => Some(Multiplexer::Screen),
+            (_, _, Some(term)) if term.starts_with("screen") => Some(Multiplexer::Screen),
+            _ => None,
+        };
+
+        Self {
+            color_depth: ColorDepth::detect(var("COLORTERM").as_deref(), term.as_deref()),
+            iterm_images: multiplexer.is_none()
+                && matches!(
+                    var("TERM_PROGRAM").as_deref(),
+                    Some("iTerm.app" | "WezTerm")
+                ),
+            multiplexer,
+            ..Self::default()
+        }
+    }
+
+    /// Function that updates the capabilities with the replies of the terminal.
+    pub fn apply_replies(&mut self, replies: &[Reply]) {
+        replies.iter().for_each(|reply| match reply {
+            Reply::DeviceAttributes(attributes) => self.sixel = attributes.contains(&4),
+            Reply::KittyGraphics(supported) => self.kitty_graphics = *supported,
+            Reply::Mode { mode, value } if *mode == SYNCHRONIZED_OUTPUT_MODE => {
+                self.synchronized_output = (1..=3).contains(value)
+            }
+            Reply::Mode { .. } => {}
+            Reply::CellSize { width, height } if *width > 0 && *height > 0 => {
+                self.cell_pixel_size = Some((*width, *height))
+            }
+            Reply::CellSize { .. } => {}
+        });
+    }
+
+    /// Function that works out what the terminal of the process can do, asking the terminal when it is
+    /// interactive. A terminal that doesn't answer in time leaves the capabilities known from the environment.
+    pub fn detect() -> Self {
+        let mut capabilities = Self::from_vars(|name| std::env::var(name).ok());
+
+        if termion::is_tty(&std::io::stdout())
+            && let Ok(bytes) = query_terminal(QUERIES, QUERY_TIMEOUT)
+        {
+            capabilities.apply_replies(&parse_replies(&bytes));
+        }
+
+        // The size reported by the kernel is preferred, some terminals answer `CSI 16 t` with a guess.
+        if let (Ok((columns, rows)), Ok((width, height))) =
+            (termion::terminal_size(), termion::terminal_size_pixels())
+            && columns > 0
+            && rows > 0
+            && width > 0
+            && height > 0
+        {
+            capabilities.cell_pixel_size =
+                Some((width as u32 / columns as u32, height as u32 / rows as u32));
+        }
+
+        capabilities
+    }
+
+    /// Function that returns the render mode that shows frames best in the terminal. Image protocols are
+    /// preferred, the ASCII encoding is used otherwise.
+    pub fn render_mode(&self, encoding: AsciiEncoding) -> RenderMode {
+        match (
+            self.multiplexer,
+            self.kitty_graphics,
+            self.iterm_images,
+            self.sixel,
+        ) {
+            (None, true, _, _) => RenderMode::Kitty(KittyPayload::Rgb),
+            (None, _, true, _) => RenderMode::Iterm(ItermFormat::Png),
+            (None, _, _, true) => RenderMode::Sixel(crate::render::sixel::DEFAULT_COLORS),
+            _ => RenderMode::Ascii(encoding),
+        }
+    }
+}
+
+/// Function that writes the queries to the terminal and returns what it answers, until it answers the device
+/// attributes query or the timeout ends. The terminal is in raw mode meanwhile so the answers aren't echoed, and
+/// it is only read when it has bytes waiting, so nothing is left reading it once this returns.
+pub fn query_terminal(
+    queries: &str,
+    timeout: Duration,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let mut tty = termion::get_tty()?.into_raw_mode()?;
+    let mut reader = termion::get_tty()?;
+
+    write!(tty, "{queries}")?;
+    tty.flush()?;
+
+    let deadline = Instant::now() + timeout;
+    let (mut bytes, mut scan, mut byte) = (Vec::new(), AttributesScan::default(), [0u8]);
+
+    // Bytes are read one at a time so the keys pressed after the last reply are left for the program.
+    while scan != AttributesScan::Done
+        && let Some(remaining) = deadline.checked_duration_since(Instant::now())
+        && wait_readable(&reader, remaining)?
+    {
+        if reader.read(&mut byte)? == 0 {
+            break;
+        }
+        bytes.push(byte[0]);
+        scan = scan.next(byte[0]);
+    }
+
+    Ok(bytes)
+}
+
+/// Function that waits until the terminal has bytes to be read, returning `false` if the timeout ends first.
+fn wait_readable(tty: &File, timeout: Duration) -> io::Result<bool> {
+    let mut poll_fd = libc::pollfd {
+        fd: tty.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let milliseconds = timeout.as_millis().clamp(1, i32::MAX as u128) as i32;
+
+    loop {
+        // SAFETY: the descriptor stays open during the call and exactly one `pollfd` is passed.
+        match unsafe { libc::poll(&mut poll_fd, 1, milliseconds) } {
+            -1 => match io::Error::last_os_error() {
+                error if error.kind() == io::ErrorKind::Interrupted => continue,
+                error => return Err(error),
+            },
+            ready => return Ok(ready > 0),
+        }
+    }
+}
+
+/// Enum that represents how much of a device attributes reply (`CSI ? Ps ; ... c`) was read, so the end of the
+/// replies is found looking at each byte once.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum AttributesScan {
+    #[default]
+    Outside,
+    Escape,
+    Introducer,
+    Parameters,
+    Done,
+}
+
+impl AttributesScan {
+    /// Function that returns the state after reading one more byte.
+    fn next(self, byte: u8) -> Self {
+        match (self, byte) {
+            (Self::Done, _) => Self::Done,
+            (_, 0x1b) => Self::Escape,
+            (Self::Escape, b'[') => Self::Introducer,
+            (Self::Introducer, b'?') => Self::Parameters,
+            (Self::Parameters, b'0'..=b'9' | b';') => Self::Parameters,
+            (Self::Parameters, b'c') => Self::Done,
+            _ => Self::Outside,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let pairs = pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<Vec<_>>();
+        move |name| {
+            pairs
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, value)| value.clone())
+        }
+    }
+
+    fn encoding() -> AsciiEncoding {
+        AsciiEncoding(vec![' ', '#'])
+    }
+
+    #[test]
+    fn parses_device_attributes() {
+        assert_eq!(
+            parse_replies(b"\x1b[?62;4;22c"),
+            [Reply::DeviceAttributes(vec![62, 4, 22])]
+        );
+
+        let mut capabilities = Capabilities::default();
+        capabilities.apply_replies(&parse_replies(b"\x1b[?62;4;22c"));
+        assert!(capabilities.sixel);
+        capabilities.apply_replies(&parse_replies(b"\x1b[?62;22c"));
+        assert!(!capabilities.sixel);
+    }
+
+    #[test]
+    fn parses_mode_reports() {
+        assert_eq!(
+            parse_replies(b"\x1b[?2026;2$y"),
+            [Reply::Mode {
+                mode: 2026,
+                value: 2
+            }]
+        );
+
+        [(2, true), (0, false), (4, false)]
+            .iter()
+            .for_each(|&(value, supported)| {
+                let mut capabilities = Capabilities::default();
+                capabilities
+                    .apply_replies(&parse_replies(format!("\x1b[?2026;{value}$y").as_bytes()));
+                assert_eq!(capabilities.synchronized_output, supported);
+            });
+    }
+
+    #[test]
+    fn finds_the_end_of_the_replies() {
+        let scan = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .scan(AttributesScan::default(), |scan, &byte| {
+                    *scan = scan.next(byte);
+                    Some(*scan == AttributesScan::Done)
+                })
+                .collect::<Vec<bool>>()
+        };
+
+        let replies = b"\x1b_Gi=31;OK\x1b\\\x1b[?2026;2$y\x1b[6;20;10tq\x1b[?62;4c";
+        let done = scan(replies);
+        // Only the final byte of the device attributes ends the replies.
+        assert_eq!(done.iter().position(|&done| done), Some(replies.len() - 1));
+
+        // Other replies and keys (`c` included) don't end them.
+        assert!(!scan(b"c\x1b[?2026;2$y\x1b[6;20;10t\x1b[c\x1bc").contains(&true));
+        assert_eq!(scan(b"\x1b[?1;2c").last(), Some(&true));
+    }
+
+    #[test]
+    fn parses_cell_size() {
+        let replies = parse_replies(b"\x1b[6;20;10t");
+        assert_eq!(
+            replies,
+            [Reply::CellSize {
+                width: 10,
+                height: 20
+            }]
+        );
+
+        let mut capabilities = Capabilities::default();
+        capabilities.apply_replies(&replies);
+        assert_eq!(capabilities.cell_pixel_size, Some((10, 20)));
+    }
+
+    #[test]
+    fn parses_kitty_graphics_replies() {
+        assert_eq!(
+            parse_replies(b"\x1b_Gi=31;OK\x1b\\"),
+            [Reply::KittyGraphics(true)]
+        );
+        assert_eq!(
+            parse_replies(b"\x1b_Gi=31;EINVAL:Unsupported action\x1b\\"),
+            [Reply::KittyGraphics(false)]
+        );
+        // Replies about other images aren't answers to the query.
+        assert_eq!(parse_replies(b"\x1b_Gi=7;OK\x1b\\"), []);
+    }
+
+    #[test]
+    fn skips_keys_between_replies() {
+        assert_eq!(
+            parse_replies(b"a\x1b_Gi=31;OK\x1b\\q\x1b[A\x1b[6;20;10tz\x1b[?62;4c"),
+            [
+                Reply::KittyGraphics(true),
+                Reply::CellSize {
+                    width: 10,
+                    height: 20
+                },
+                Reply::DeviceAttributes(vec![62, 4]),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_replies_cut_at_the_end() {
+        assert_eq!(
+            parse_replies(b"\x1b[6;20;10t\x1b[?62;4"),
+            [Reply::CellSize {
+                width: 10,
+                height: 20
+            }]
+        );
+        assert_eq!(parse_replies(b"\x1b_Gi=31;OK\x1b"), []);
+        assert_eq!(parse_replies(b"\x1b"), []);
+    }
+
+    #[test]
+    fn detects_multiplexers() {
+        assert_eq!(
+            Capabilities::from_vars(vars(&[("TMUX", "/tmp/tmux-1000/default,1,0")])).multiplexer,
+            Some(Multiplexer::Tmux)
+        );
+        assert_eq!(
+            Capabilities::from_vars(vars(&[("STY", "1234.pts-0.host")])).multiplexer,
+            Some(Multiplexer::Screen)
+        );
+        assert_eq!(
+            Capabilities::from_vars(vars(&[("TERM", "screen-256color")])).multiplexer,
+            Some(Multiplexer::Screen)
+        );
+        assert_eq!(
+            Capabilities::from_vars(vars(&[("TERM", "xterm-256color")])).multiplexer,
+            None
+        );
+    }
+
+    #[test]
+    fn detects_iterm_images_outside_multiplexers() {
+        assert!(Capabilities::from_vars(vars(&[("TERM_PROGRAM", "iTerm.app")])).iterm_images);
+        assert!(
+            !Capabilities::from_vars(vars(&[("TERM_PROGRAM", "iTerm.app"), ("TMUX", "1")]))
+                .iterm_images
+        );
+    }
+
+    #[test]
+    fn prefers_image_protocols() {
+        let all = Capabilities {
+            sixel: true,
+            kitty_graphics: true,
+            iterm_images: true,
+            ..Capabilities::default()
+        };
+        assert!(matches!(
+            all.render_mode(encoding()),
+            RenderMode::Kitty(KittyPayload::Rgb)
+        ));
+
+        let iterm_and_sixel = Capabilities {
+            kitty_graphics: false,
+            ..all
+        };
+        assert!(matches!(
+            iterm_and_sixel.render_mode(encoding()),
+            RenderMode::Iterm(ItermFormat::Png)
+        ));
+
+        let sixel = Capabilities {
+            iterm_images: false,
+            ..iterm_and_sixel
+        };
+        assert!(matches!(
+            sixel.render_mode(encoding()),
+            RenderMode::Sixel(crate::render::sixel::DEFAULT_COLORS)
+        ));
+
+        assert!(matches!(
+            Capabilities::default().render_mode(encoding()),
+            RenderMode::Ascii(_)
+        ));
+    }
+
+    #[test]
+    fn uses_ascii_inside_multiplexers() {
+        let capabilities = Capabilities {
+            sixel: true,
+            kitty_graphics: true,
+            multiplexer: Some(Multiplexer::Tmux),
+            ..Capabilities::default()
+        };
+
+        assert!(matches!(
+            capabilities.render_mode(encoding()),
+            RenderMode::Ascii(_)
+        ));
+    }
+}
@@ -0,0 +1,224 @@
+//! Module that implements the thread that captures frames from a feed source, so the blocking capture calls don't
+//! stall the async runtime.
+
+use crate::feed::FeedSource;
+use crate::switch::SourceSwitch;
+use image::RgbImage;
+use std::error::Error;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use triple_buffer::{Input, Output};
+
+/// Longest time a reader waits for a frame before checking the end flag again, since it isn't woken when it is set.
+pub const END_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Struct that represents a thread that captures frames from a source at most at the given frame rate and publishes
+/// the latest one through a triple buffer, so readers always get the newest frame without waiting for the source.
+/// The thread stops when the struct is dropped.
+pub struct CaptureThread {
+    /// Latest frame with its sequence number, which tells how many frames were overwritten before being read.
+    output: Output<Option<(u64, RgbImage)>>,
+    last_sequence: u64,
+    overwritten: u64,
+    /// Woken by the thread after each frame it publishes and when it ends.
+    published: Arc<Notify>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<Result<(), Box<dyn Error + Send + Sync>>>>,
+}
+
+impl CaptureThread {
+    /// Function that starts capturing from a source, moving to any source sent through the switch.
+    pub fn spawn<T: FeedSource>(
+        source: T,
+        switch: SourceSwitch<T>,
+        frame_rate: u32,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let (input, output) = triple_buffer::triple_buffer(&None);
+        let published = Arc::new(Notify::new());
+        let stop = Arc::new(AtomicBool::new(false));
+        let interval = Duration::from_secs_f64(1. / frame_rate.max(1) as f64);
+
+        let (thread_published, thread_stop) = (published.clone(), stop.clone());
+        let handle = thread::Builder::new()
+            .name("capture".into())
+            .spawn(move || {
+                let result = capture(
+                    source,
+                    switch,
+                    input,
+                    interval,
+                    &thread_published,
+                    &thread_stop,
+                );
+                // The reader learns that the thread ended (e.g. because of an error) without waiting for a frame.
+                thread_published.notify_one();
+                result
+            })?;
+
+        Ok(Self {
+            output,
+            last_sequence: 0,
+            overwritten: 0,
+            published,
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    /// Function that returns the latest frame if one was published since the last call. The frame is moved out of
+    /// the buffer, not copied.
+    pub fn latest(&mut self) -> Option<RgbImage> {
+        if !self.output.update() {
+            return None;
+        }
+
+        let (sequence, frame) = self.output.output_buffer_mut().take()?;
+        self.overwritten += sequence.saturating_sub(self.last_sequence + 1);
+        self.last_sequence = sequence;
+
+        Some(frame)
+    }
+
+    /// Function that returns how many frames were captured but replaced by a newer one before being read since the
+    /// last call.
+    pub fn take_overwritten(&mut self) -> u64 {
+        std::mem::take(&mut self.overwritten)
+    }
+
+    /// Function that waits for a frame newer than the last one returned, or returns `None` once the end flag is set.
+    /// Fails with the error of the source if the capture stopped because of it.
+    pub async fn next_frame(
+        &mut self,
+        end_flag: &AtomicBool,
+    ) -> Result<Option<RgbImage>, Box<dyn Error + Send + Sync>> {
+        while !end_flag.load(Ordering::Acquire) {
+            if let Some(frame) = self.latest() {
+                return Ok(Some(frame));
+            }
+
+            if self
+                .handle
+                .as_ref()
+                .is_some_and(|handle| handle.is_finished())
+            {
+                return match self.handle.take().map(|handle| handle.join()) {
+                    Some(Ok(result)) => result.map(|_| None),
+                    _ => Err("The capture thread panicked.".into()),
+                };
+            }
+
+            let _ = tokio::time::timeout(END_CHECK_INTERVAL, self.published.notified()).await;
+        }
+
+        Ok(None)
+    }
+}
+
+impl Drop for CaptureThread {
+    fn drop(&mut self) {
+        // The thread isn't joined, it ends by itself once its current capture returns.
+        self.stop.store(true, Ordering::Release);
+    }
+}
+
+/// Function that captures frames until it is stopped, publishing each one and waking the reader.
+fn capture<T: FeedSource>(
+    mut source: T,
+    switch: SourceSwitch<T>,
+    mut input: Input<Option<(u64, RgbImage)>>,
+    interval: Duration,
+    published: &Notify,
+    stop: &AtomicBool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut next_capture = Instant::now();
+    let mut sequence = 0;
+
+    while !stop.load(Ordering::Acquire) {
+        if let Some(new_source) = switch.take() {
+            source = new_source;
+        }
+
+        sequence += 1;
+        input.write(Some((sequence, source.get_frame_rgb()?)));
+        published.notify_one();
+
+        // Sources that return right away (e.g. images) are not captured faster than the frame rate.
+        next_capture += interval;
+        match next_capture.checked_duration_since(Instant::now()) {
+            Some(wait) => thread::sleep(wait),
+            None => next_capture = Instant::now(),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_pattern::{DEFAULT_SEED, Pattern, TestPattern};
+    use image::{ImageBuffer, Rgb};
+
+    /// Struct that represents a source that fails after a number of frames.
+    struct Failing(usize);
+
+    impl FeedSource for Failing {
+        fn get_frame_rgb(
+            &mut self,
+        ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, Box<dyn Error + Send + Sync>> {
+            match self.0 {
+                0 => Err("The source failed.".into()),
+                _ => {
+                    self.0 -= 1;
+                    Ok(RgbImage::new(1, 1))
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn publishes_new_frames() {
+        let end_flag = AtomicBool::new(false);
+        let source = TestPattern::with_seed(Pattern::default(), DEFAULT_SEED);
+        let mut capture = CaptureThread::spawn(source, SourceSwitch::new(), 1000).unwrap();
+
+        let first = capture.next_frame(&end_flag).await.unwrap().unwrap();
+        let second = capture.next_frame(&end_flag).await.unwrap().unwrap();
+        assert_ne!(first, second);
+
+        end_flag.store(true, Ordering::Release);
+        assert!(capture.next_frame(&end_flag).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn counts_overwritten_frames() {
+        let end_flag = AtomicBool::new(false);
+        let mut capture = CaptureThread::spawn(Failing(3), SourceSwitch::new(), 1000).unwrap();
+
+        // The thread ends after publishing its three frames, so only the last one is left to be read.
+        while capture
+            .handle
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+        {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        assert!(capture.next_frame(&end_flag).await.unwrap().is_some());
+        assert_eq!(capture.take_overwritten(), 2);
+        assert_eq!(capture.take_overwritten(), 0);
+    }
+
+    #[tokio::test]
+    async fn returns_the_error_of_the_source_after_its_frames() {
+        let end_flag = AtomicBool::new(false);
+        let mut capture = CaptureThread::spawn(Failing(1), SourceSwitch::new(), 1000).unwrap();
+
+        // The frame published before the error is still read.
+        assert!(capture.next_frame(&end_flag).await.unwrap().is_some());
+        let error = capture.next_frame(&end_flag).await.unwrap_err();
+        assert_eq!(error.to_string(), "The source failed.");
+    }
+}
@@ -0,0 +1,151 @@
+//! Module that composes two feeds into one (e.g. a shared screen with a webcam inset).
+
+use crate::{
+    FILTER,
+    config::FeedConfig,
+    feed::{Feed, FeedConstructor, FeedSource},
+    screen_capture::fit_size,
+};
+use image::{ImageBuffer, Rgb};
+use std::{error::Error, str::FromStr};
+
+/// Enum that represents the corner of the main frame where the inset is placed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+impl FromStr for Corner {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "top-left" => Ok(Self::TopLeft),
+            "top-right" => Ok(Self::TopRight),
+            "bottom-left" => Ok(Self::BottomLeft),
+            "bottom-right" => Ok(Self::BottomRight),
+            _ => Err(format!(
+                "Invalid corner `{s}`, expected top-left, top-right, bottom-left or bottom-right."
+            )
+            .into()),
+        }
+    }
+}
+
+/// Struct that represents where and how the inset is drawn on top of the main frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InsetLayout {
+    pub corner: Corner,
+    /// Width of the inset as a fraction of the width of the main frame (the height follows its aspect ratio).
+    pub scale: f32,
+    /// Distance, in pixels, between the inset and the edges of the main frame.
+    pub margin: u32,
+    /// Width, in pixels, of the border drawn around the inset.
+    pub border: u32,
+    pub border_color: Rgb<u8>,
+}
+
+impl Default for InsetLayout {
+    fn default() -> Self {
+        Self {
+            corner: Corner::default(),
+            scale: 0.25,
+            margin: 8,
+            border: 2,
+            border_color: Rgb([255, 255, 255]),
+        }
+    }
+}
+
+/// Struct that represents a feed that draws a small inset feed in a corner of a main feed and sends both as one frame.
+pub struct PictureInPicture<M, I> {
+    pub main: M,
+    pub inset: I,
+    pub layout: InsetLayout,
+}
+
+impl<M: FeedSource, I: FeedSource> PictureInPicture<M, I> {
+    /// Function that creates a composite feed from two already created sources.
+    pub fn with_sources(main: M, inset: I, layout: InsetLayout) -> Self {
+        Self {
+            main,
+            inset,
+            layout,
+        }
+    }
+
+    /// Function that draws the inset and its border on top of the main frame.
+    fn compose(
+        &self,
+        main: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+        inset: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    ) {
+        let layout = &self.layout;
+        let (main_width, main_height) = main.dimensions();
+
+        let bounds = (
+            ((main_width as f32 * layout.scale.clamp(0., 1.)) as u32).max(1),
+            main_height,
+        );
+        let (width, height) = fit_size(inset.dimensions(), bounds);
+        let inset = image::imageops::resize(inset, width, height, FILTER);
+
+        let (outer_width, outer_height) = (width + 2 * layout.border, height + 2 * layout.border);
+        let x = match layout.corner {
+            Corner::TopLeft | Corner::BottomLeft => layout.margin as i64,
+            Corner::TopRight | Corner::BottomRight => {
+                main_width as i64 - outer_width as i64 - layout.margin as i64
+            }
+        };
+        let y = match layout.corner {
+            Corner::TopLeft | Corner::TopRight => layout.margin as i64,
+            Corner::BottomLeft | Corner::BottomRight => {
+                main_height as i64 - outer_height as i64 - layout.margin as i64
+            }
+        };
+
+        if layout.border > 0 {
+            let border = ImageBuffer::from_pixel(outer_width, outer_height, layout.border_color);
+            image::imageops::replace(main, &border, x, y);
+        }
+        image::imageops::replace(
+            main,
+            &inset,
+            x + layout.border as i64,
+            y + layout.border as i64,
+        );
+    }
+}
+
+impl<M: Feed, I: Feed> Feed for PictureInPicture<M, I> {
+    fn default_config() -> FeedConfig {
+        M::default_config()
+    }
+}
+
+impl<M: FeedConstructor, I: FeedConstructor> FeedConstructor for PictureInPicture<M, I> {
+    fn new(config: &FeedConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self {
+            main: M::new(config)?,
+            inset: I::new(config)?,
+            layout: InsetLayout::default(),
+        })
+    }
+}
+
+impl<M: FeedSource, I: FeedSource> FeedSource for PictureInPicture<M, I> {
+    fn get_frame_rgb(
+        &mut self,
+    ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, Box<dyn Error + Send + Sync>> {
+        let mut frame = self.main.get_frame_rgb()?;
+        let inset = self.inset.get_frame_rgb()?;
+
+        self.compose(&mut frame, &inset);
+
+        Ok(frame)
+    }
+}
@@ -0,0 +1,310 @@
+//! Module that implements the runtime configuration of the feeds and how it is read from the command line or a file.
+
+use bincode::{Decode, Encode};
+use std::{error::Error, fs, path::Path, time::Duration};
+
+/// Maximum payload of a UDP datagram over IPv4, frames sent in a stream must fit in it.
+pub const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// Function that removes `OPTION VALUE` from the arguments and returns the value, if the option is present.
+pub fn take_option(
+    args: &mut Vec<String>,
+    option: &str,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    let position = match args.iter().position(|arg| arg == option) {
+        Some(position) => position,
+        None => return Ok(None),
+    };
+
+    if position + 1 >= args.len() {
+        return Err(format!("Missing value for `{option}`.").into());
+    }
+
+    let value = args.remove(position + 1);
+    args.remove(position);
+
+    Ok(Some(value))
+}
+
+/// Function that parses a size written as `WIDTHxHEIGHT` (e.g. `320x240`).
+pub fn parse_geometry(geometry: &str) -> Result<(u32, u32), Box<dyn Error + Send + Sync>> {
+    let invalid = || format!("Invalid size `{geometry}`, expected WIDTHxHEIGHT.");
+    let (width, height) = geometry.split_once(['x', 'X']).ok_or_else(invalid)?;
+
+    Ok((
+        width.trim().parse().map_err(|_| invalid())?,
+        height.trim().parse().map_err(|_| invalid())?,
+    ))
+}
+
+/// Function that removes a flag without a value from the arguments and returns whether it was present.
+pub fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let length = args.len();
+    args.retain(|arg| arg != flag);
+
+    args.len() != length
+}
+
+/// Enum that represents the byte order used to encode frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Endianness {
+    #[default]
+    Little,
+    Big,
+}
+
+/// Enum that represents how integers are encoded in frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum IntEncoding {
+    #[default]
+    Varint,
+    Fixed,
+}
+
+/// Struct that represents the configuration used to encode frames into bytes. Both peers of a stream must use the same one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct EncodeConfig {
+    pub endianness: Endianness,
+    pub int_encoding: IntEncoding,
+}
+
+impl EncodeConfig {
+    /// Function that encodes a value into bytes.
+    pub fn encode<E: Encode>(&self, value: E) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        let standard = bincode::config::standard();
+
+        Ok(match (self.endianness, self.int_encoding) {
+            (Endianness::Little, IntEncoding::Varint) => bincode::encode_to_vec(value, standard)?,
+            (Endianness::Little, IntEncoding::Fixed) => {
+                bincode::encode_to_vec(value, standard.with_fixed_int_encoding())?
+            }
+            (Endianness::Big, IntEncoding::Varint) => {
+                bincode::encode_to_vec(value, standard.with_big_endian())?
+            }
+            (Endianness::Big, IntEncoding::Fixed) => {
+                bincode::encode_to_vec(value, standard.with_big_endian().with_fixed_int_encoding())?
+            }
+        })
+    }
+
+    /// Function that decodes a value from bytes.
+    pub fn decode<D: Decode<()>>(&self, bytes: &[u8]) -> Result<D, Box<dyn Error + Send + Sync>> {
+        let standard = bincode::config::standard();
+
+        let (decoded, _) = match (self.endianness, self.int_encoding) {
+            (Endianness::Little, IntEncoding::Varint) => {
+                bincode::decode_from_slice(bytes, standard)?
+            }
+            (Endianness::Little, IntEncoding::Fixed) => {
+                bincode::decode_from_slice(bytes, standard.with_fixed_int_encoding())?
+            }
+            (Endianness::Big, IntEncoding::Varint) => {
+                bincode::decode_from_slice(bytes, standard.with_big_endian())?
+            }
+            (Endianness::Big, IntEncoding::Fixed) => bincode::decode_from_slice(
+                bytes,
+                standard.with_big_endian().with_fixed_int_encoding(),
+            )?,
+        };
+
+        Ok(decoded)
+    }
+}
+
+/// Struct that represents the runtime configuration of a feed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeedConfig {
+    /// Rate at which the feed's frames are displayed on the terminal.
+    pub frame_rate: u32,
+
+    /// Configuration used to encode frames into bytes.
+    pub encode_config: EncodeConfig,
+
+    /// In case of communication failure, the time the system should wait for the connection to reappear.
+    pub timeout: Duration,
+
+    /// The size of the frame that will be encoded and sent as an UDP packet.
+    pub stream_frame_size: (u32, u32),
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            frame_rate: 600,
+            encode_config: EncodeConfig::default(),
+            timeout: Duration::from_secs(1),
+            stream_frame_size: (60, 30),
+        }
+    }
+}
+
+impl FeedConfig {
+    /// Options accepted on the command line (prefixed with `--`) and as keys in a configuration file.
+    pub const OPTIONS: [&str; 5] = [
+        "frame-rate",
+        "timeout-ms",
+        "stream-size",
+        "endianness",
+        "int-encoding",
+    ];
+
+    /// Function that checks that the configuration can be used.
+    pub fn validate(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (width, height) = self.stream_frame_size;
+
+        if self.frame_rate == 0 {
+            return Err("The frame rate must be greater than zero.".into());
+        }
+        if self.timeout.is_zero() {
+            return Err("The timeout must be greater than zero.".into());
+        }
+        if width == 0 || height == 0 {
+            return Err("The stream frame size must be greater than zero.".into());
+        }
+        // Each pixel takes four bytes, plus the frame size and the length of the pixel vector.
+        if width as usize * height as usize * 4 + 16 > MAX_DATAGRAM_SIZE {
+            return Err(format!(
+                "A {width}x{height} stream frame doesn't fit in a {MAX_DATAGRAM_SIZE} byte datagram."
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Function that sets one option from its name and textual value.
+    pub fn set(&mut self, option: &str, value: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match option {
+            "frame-rate" => self.frame_rate = value.parse()?,
+            "timeout-ms" => self.timeout = Duration::from_millis(value.parse()?),
+            "stream-size" => self.stream_frame_size = parse_geometry(value)?,
+            "endianness" => {
+                self.encode_config.endianness = match value {
+                    "little" => Endianness::Little,
+                    "big" => Endianness::Big,
+                    _ => {
+                        return Err(format!(
+                            "Invalid endianness `{value}`, expected little or big."
+                        )
+                        .into());
+                    }
+                }
+            }
+            "int-encoding" => {
+                self.encode_config.int_encoding = match value {
+                    "varint" => IntEncoding::Varint,
+                    "fixed" => IntEncoding::Fixed,
+                    _ => {
+                        return Err(format!(
+                            "Invalid int encoding `{value}`, expected varint or fixed."
+                        )
+                        .into());
+                    }
+                }
+            }
+            _ => return Err(format!("Unknown option `{option}`.").into()),
+        }
+
+        Ok(())
+    }
+
+    /// Function that applies a configuration file on top of this configuration. The file has one `option = value`
+    /// per line, blank lines and lines starting with `#` are ignored.
+    pub fn load_file(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let contents = fs::read_to_string(path)?;
+
+        contents
+            .lines()
+            .map(str::trim)
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .try_for_each(|(i, line)| {
+                let (option, value) = line.split_once('=').ok_or_else(|| {
+                    format!(
+                        "Line {} of the configuration file is not `option = value`.",
+                        i + 1
+                    )
+                })?;
+                self.set(option.trim(), value.trim().trim_matches('"'))
+            })
+    }
+
+    /// Function that applies the command line arguments on top of this configuration and validates the result.
+    /// `--config PATH` loads a file before the options that follow it. Arguments that are not configuration
+    /// options are returned so the caller can interpret them.
+    pub fn parse_args(
+        mut self,
+        args: impl IntoIterator<Item = String>,
+    ) -> Result<(Self, Vec<String>), Box<dyn Error + Send + Sync>> {
+        let mut args = args.into_iter();
+        let mut remaining = Vec::new();
+
+        while let Some(arg) = args.next() {
+            let option = match arg.strip_prefix("--") {
+                Some(option) if option == "config" || Self::OPTIONS.contains(&option) => {
+                    option.to_string()
+                }
+                _ => {
+                    remaining.push(arg);
+                    continue;
+                }
+            };
+
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value for `--{option}`."))?;
+
+            match option.as_str() {
+                "config" => self.load_file(value)?,
+                _ => self.set(&option, &value)?,
+            }
+        }
+
+        self.validate()?;
+
+        Ok((self, remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_geometry() {
+        assert_eq!(parse_geometry("320x240").unwrap(), (320, 240));
+        assert_eq!(parse_geometry("64X48").unwrap(), (64, 48));
+        assert_eq!(parse_geometry(" 1 x 2 ").unwrap(), (1, 2));
+
+        ["", "320", "320x", "x240", "-1x2", "axb", "1x2x3"]
+            .iter()
+            .for_each(|geometry| {
+                assert_eq!(
+                    parse_geometry(geometry).unwrap_err().to_string(),
+                    format!("Invalid size `{geometry}`, expected WIDTHxHEIGHT.")
+                )
+            });
+    }
+
+    #[test]
+    fn uses_the_geometry_for_the_stream_size() {
+        let mut config = FeedConfig::default();
+        config.set("stream-size", "80x60").unwrap();
+        assert_eq!(config.stream_frame_size, (80, 60));
+        assert!(config.set("stream-size", "80").is_err());
+    }
+
+    #[test]
+    fn takes_options_and_flags() {
+        let mut args = ["--a", "1", "--flag", "--b"].map(String::from).to_vec();
+
+        assert_eq!(take_option(&mut args, "--a").unwrap().as_deref(), Some("1"));
+        assert!(take_flag(&mut args, "--flag"));
+        assert!(!take_flag(&mut args, "--flag"));
+        assert!(take_option(&mut args, "--b").is_err());
+        assert_eq!(args, ["--b"]);
+    }
+}
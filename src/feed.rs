@@ -1,130 +1,176 @@
 //! Module where image rendering, encoding, compression and streaming are implemented.
 
 use crate::FILTER;
-use crate::feed::frame::{AsciiEncoding, Frame, Image};
+use crate::capture::CaptureThread;
+use crate::config::{FeedConfig, MAX_DATAGRAM_SIZE};
+use crate::feed::frame::{Frame, Image};
+use crate::render::Renderer;
+use crate::switch::SourceSwitch;
 use async_rate_limiter::RateLimiter;
 use async_trait::async_trait;
-use bincode::config::Configuration;
 use image::imageops::colorops::{brighten_in_place, contrast_in_place};
 use image::{DynamicImage, ImageBuffer, Rgb};
 use std::error::Error;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
-use std::time::Duration;
-use termcolor::BufferWriter;
+use std::time::Instant;
+use termcolor::{Buffer, BufferWriter};
 use tokio::net::UdpSocket;
 use tokio::time::timeout;
 
-/// Trait that unifies how the feed is manipulated for all sources (e.g. webcam or screen sharing).
-#[async_trait]
-pub trait Feed: 'static {
-    /// Rate at which the feed's frames are displayed on the terminal.
-    const FRAME_RATE: u32;
-
-    /// Configuration used to encode frames into bytes.
-    const ENCODE_CONFIG: Configuration;
-
-    /// In case of communication failure, the time the system should wait for the connection to reappear.
-    const TIMEOUT_DURATION: Duration;
-
-    /// The size of the frame that will be encoded and sent as an UDP packet.
-    const STREAM_FRAME_SIZE: (u32, u32) = (60, 30);
-
-    /// Function that creates a new feed source.
-    fn new() -> Result<Self, Box<dyn Error + Send + Sync>>
-    where
-        Self: Sized;
-
+/// Trait that captures frames from a source. It is object-safe so the source can be chosen at runtime
+/// and used as a `Box<dyn FeedSource>`.
+pub trait FeedSource: Send + 'static {
     /// Function that returns a frame using the API of the feed source.
     fn get_frame_rgb(
         &mut self,
     ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, Box<dyn Error + Send + Sync>>;
+}
+
+impl FeedSource for Box<dyn FeedSource> {
+    fn get_frame_rgb(
+        &mut self,
+    ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, Box<dyn Error + Send + Sync>> {
+        (**self).get_frame_rgb()
+    }
+}
+
+/// Trait that unifies how the feed is manipulated for all sources (e.g. webcam or screen sharing).
+#[async_trait]
+pub trait Feed: FeedSource {
+    /// Function that returns the configuration that suits the feed source best, used when none is given.
+    fn default_config() -> FeedConfig
+    where
+        Self: Sized,
+    {
+        FeedConfig::default()
+    }
 
     /// Function that resizes the frame to fill the terminal and applies effects on the frame to make it more visible.
     fn preprocess_frame(
         rgb: ImageBuffer<Rgb<u8>, Vec<u8>>,
+        renderer: &Renderer,
     ) -> Result<Frame, Box<dyn Error + Send + Sync>> {
-        let (mut rgb, x, y) = Image(rgb).image_to_terminal_size();
+        let (mut rgb, x, y) = Image(rgb).image_to_terminal_size(renderer);
 
-        brighten_in_place(rgb.buffer_mut(), 40);
+        // The adjustments make up for the coarse text renderers, pixel images are shown as captured.
+        let image = renderer.mode.is_image();
+        if !image {
+            brighten_in_place(rgb.buffer_mut(), 40);
+        }
 
         let mut luma = DynamicImage::ImageRgb8(rgb.buffer().clone()).into_luma8();
-        brighten_in_place(&mut luma, 20);
-        contrast_in_place(&mut luma, 10.);
+        if !image {
+            brighten_in_place(&mut luma, 20);
+            contrast_in_place(&mut luma, 10.);
+        }
 
         let frame = Frame::new(luma, rgb.buffer_consume(), x, y);
 
         Ok(frame)
     }
 
-    /// Function that displays feed in the terminal (uses the alternative stdout).
-    async fn show(
+    /// Function that displays the feed of an already created source in the terminal (uses the alternative stdout).
+    async fn show_from(
+        feed_source: Self,
         buffer_writer: BufferWriter,
-        encoding: AsciiEncoding,
+        mut renderer: Renderer,
+        config: FeedConfig,
         end_flag: Arc<AtomicBool>,
     ) -> Result<(), Box<dyn Error + Send + Sync>>
     where
         Self: Sized,
     {
-        let mut feed_source = Self::new()?;
-        let rate_limiter = RateLimiter::new(Self::FRAME_RATE as usize);
-
-        let (mut input_buffer, mut output_buffer) =
-            triple_buffer::triple_buffer(&buffer_writer.buffer());
-
-        while end_flag.load(std::sync::atomic::Ordering::Acquire) == false {
-            rate_limiter.acquire().await;
+        config.validate()?;
+        let mut capture =
+            CaptureThread::spawn(feed_source, SourceSwitch::new(), config.frame_rate)?;
+
+        while let Some(mut rgb) = capture.next_frame(&end_flag).await? {
+            // While the terminal is busy the capture keeps going, and the newest frame by the time it is ready is
+            // drawn instead of this one.
+            let wait = renderer.pacer.time_until_ready(Instant::now());
+            if !wait.is_zero() {
+                tokio::time::sleep(wait).await;
+                if let Some(newer) = capture.latest() {
+                    renderer.stats.record_dropped(1);
+                    rgb = newer;
+                }
+            }
+            renderer.stats.record_dropped(capture.take_overwritten());
 
-            let rgb = feed_source.get_frame_rgb()?;
-            let frame = Self::preprocess_frame(rgb)?;
+            let frame = Self::preprocess_frame(rgb, &renderer)?;
 
             let mut buffer = buffer_writer.buffer();
-            frame.load_buffer(&encoding, &mut buffer)?;
-            input_buffer.write(buffer);
+            frame.load_buffer(&mut renderer, &mut buffer)?;
 
-            buffer_writer.print(&output_buffer.read())?;
+            Self::print_paced(&buffer_writer, &buffer, &mut renderer)?;
         }
 
         Ok(())
     }
 
     /// Function that encodes a frame into bytes.
-    fn encode_frame(frame: Frame) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
-        Ok(bincode::encode_to_vec(frame, Self::ENCODE_CONFIG)?)
+    fn encode_frame(
+        frame: Frame,
+        config: &FeedConfig,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        config.encode_config.encode(frame)
     }
 
     /// Function that dencodes a frame from bytes.
-    fn decode_frame(bytes: &[u8]) -> Result<Frame, Box<dyn Error + Send + Sync>> {
-        let (decoded, _): (Frame, usize) =
-            bincode::decode_from_slice(&bytes[..], Self::ENCODE_CONFIG)?;
-
-        Ok(decoded)
+    fn decode_frame(
+        bytes: &[u8],
+        config: &FeedConfig,
+    ) -> Result<Frame, Box<dyn Error + Send + Sync>> {
+        config.encode_config.decode(bytes)
     }
 
-    /// Function that streams the feed using UDP Socket communication.
-    async fn stream(
+    /// Function that streams the feed of an already created source using UDP Socket communication.
+    async fn stream_from(
+        feed_source: Self,
         connection: UdpSocket,
+        config: FeedConfig,
         end_flag: Arc<AtomicBool>,
     ) -> Result<(), Box<dyn Error + Send + Sync>>
     where
         Self: Sized,
     {
-        let mut feed_source = Self::new()?;
+        Self::stream_switchable(
+            feed_source,
+            SourceSwitch::new(),
+            connection,
+            config,
+            end_flag,
+        )
+        .await
+    }
 
-        let (mut input_buffer, mut output_buffer) = triple_buffer::triple_buffer(&Vec::new());
+    /// Function that streams the feed using UDP Socket communication, moving to any source sent through the switch
+    /// while keeping the same connection.
+    async fn stream_switchable(
+        feed_source: Self,
+        switch: SourceSwitch<Self>,
+        connection: UdpSocket,
+        config: FeedConfig,
+        end_flag: Arc<AtomicBool>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>>
+    where
+        Self: Sized,
+    {
+        config.validate()?;
+        let mut capture = CaptureThread::spawn(feed_source, switch, config.frame_rate)?;
 
-        while end_flag.load(std::sync::atomic::Ordering::Acquire) == false {
-            let rgb = feed_source.get_frame_rgb()?;
+        while let Some(rgb) = capture.next_frame(&end_flag).await? {
             let frame = Image(image::imageops::resize(
                 &rgb,
-                Self::STREAM_FRAME_SIZE.0,
-                Self::STREAM_FRAME_SIZE.1,
+                config.stream_frame_size.0,
+                config.stream_frame_size.1,
                 FILTER,
             ))
             .into_frame();
-            input_buffer.write(Self::encode_frame(frame)?);
-
-            connection.send(&output_buffer.read()).await?;
+            connection
+                .send(&Self::encode_frame(frame, &config)?)
+                .await?;
         }
 
         Ok(())
@@ -134,50 +180,104 @@ pub trait Feed: 'static {
     async fn show_stream(
         buffer_writer: BufferWriter,
         connection: UdpSocket,
-        encoding: &AsciiEncoding,
+        mut renderer: Renderer,
+        config: FeedConfig,
         end_flag: Arc<AtomicBool>,
     ) -> Result<(), Box<dyn Error + Send + Sync>>
     where
         Self: Sized,
     {
-        let rate_limiter = RateLimiter::new(Self::FRAME_RATE as usize);
-
-        let (mut input_buffer, mut output_buffer) =
-            triple_buffer::triple_buffer(&buffer_writer.buffer());
+        config.validate()?;
+        let rate_limiter = RateLimiter::new(config.frame_rate as usize);
 
-        let mut buffer_temp = vec![0u8; 65_507];
+        let mut buffer_temp = vec![0u8; MAX_DATAGRAM_SIZE];
 
         while end_flag.load(std::sync::atomic::Ordering::Acquire) == false {
             rate_limiter.acquire().await;
 
-            if let Err(_) = timeout(Self::TIMEOUT_DURATION, connection.recv(&mut buffer_temp)).await
-            {
+            if let Err(_) = timeout(config.timeout, connection.recv(&mut buffer_temp)).await {
                 continue;
             }
-            let (resized_image, _, _) = Self::decode_frame(&buffer_temp)?
+            // The datagram is still read when the frame is dropped, so the next one received is the freshest.
+            if !renderer.pacer.is_ready(Instant::now()) {
+                renderer.stats.record_dropped(1);
+                continue;
+            }
+            let (resized_image, _, _) = Self::decode_frame(&buffer_temp, &config)?
                 .into_image()
-                .image_to_terminal_size();
+                .image_to_terminal_size(&renderer);
             let frame = resized_image.into_frame();
 
             let mut buffer = buffer_writer.buffer();
-            frame.load_buffer(&encoding, &mut buffer)?;
-            input_buffer.write(buffer);
+            frame.load_buffer(&mut renderer, &mut buffer)?;
 
-            buffer_writer.print(&output_buffer.read())?;
+            Self::print_paced(&buffer_writer, &buffer, &mut renderer)?;
         }
 
         Ok(())
     }
+
+    /// Function that prints a frame and records how long the terminal took to take it, which decides when the next
+    /// frame can be drawn.
+    fn print_paced(
+        buffer_writer: &BufferWriter,
+        buffer: &Buffer,
+        renderer: &mut Renderer,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let start = Instant::now();
+        buffer_writer.print(buffer)?;
+        renderer.pacer.record_print(start, Instant::now());
+
+        Ok(())
+    }
 }
 
+/// Trait for the feed sources that can be created from the configuration alone (e.g. the primary monitor or the
+/// first camera). Sources that need an argument (e.g. the path of a file) are created with their own functions and
+/// shown or streamed with the `*_from` feed functions.
+#[async_trait]
+pub trait FeedConstructor: Feed + Sized {
+    /// Function that creates a new feed source.
+    fn new(config: &FeedConfig) -> Result<Self, Box<dyn Error + Send + Sync>>;
+
+    /// Function that displays feed in the terminal (uses the alternative stdout).
+    async fn show(
+        buffer_writer: BufferWriter,
+        renderer: Renderer,
+        config: FeedConfig,
+        end_flag: Arc<AtomicBool>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let feed_source = Self::new(&config)?;
+        Self::show_from(feed_source, buffer_writer, renderer, config, end_flag).await
+    }
+
+    /// Function that streams the feed using UDP Socket communication.
+    async fn stream(
+        connection: UdpSocket,
+        config: FeedConfig,
+        end_flag: Arc<AtomicBool>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let feed_source = Self::new(&config)?;
+        Self::stream_from(feed_source, connection, config, end_flag).await
+    }
+}
+
+/// Dynamic sources are created through `registry::SourceRegistry`.
+impl Feed for Box<dyn FeedSource> {}
+
 /// Module that implements methods for frame and image manipulation.
 pub mod frame {
-    use crate::FILTER;
+    use crate::render::color::ColorDepth;
+    use crate::render::grid::{Cell, CellGrid};
+    use crate::render::{
+        BEGIN_SYNCHRONIZED_UPDATE, END_SYNCHRONIZED_UPDATE, RenderMode, Renderer, braille,
+        half_block, iterm, kitty, mosaic, sixel,
+    };
     use bincode::{Decode, Encode};
     use image::{DynamicImage, ImageBuffer, Luma, Rgb};
     use std::error::Error;
     use std::io::Write;
-    use termcolor::{Buffer, Color, ColorSpec, WriteColor};
+    use termcolor::Buffer;
     use termion::terminal_size;
 
     /// Struct that represents the size of the frame.
@@ -198,10 +298,10 @@ pub mod frame {
     /// (red, green and blue) and a greyscale value for the brightness.
     #[derive(Encode, Decode)]
     pub struct Pixel {
-        red: u8,
-        green: u8,
-        blue: u8,
-        grey_scale: u8,
+        pub red: u8,
+        pub green: u8,
+        pub blue: u8,
+        pub grey_scale: u8,
     }
 
     /// Struct that represents a collection of pixels.
@@ -240,34 +340,115 @@ pub mod frame {
         /// Function that loads a buffer using the information in the frame to then be displayed.
         pub fn load_buffer(
             &self,
-            encoding: &AsciiEncoding,
+            renderer: &mut Renderer,
             buffer: &mut Buffer,
         ) -> Result<(), Box<dyn Error + Send + Sync>> {
-            write!(buffer, "{}", termion::clear::AfterCursor)?;
-
-            self.pixels.iter().enumerate().try_for_each(
-                |(i, pixel)| -> Result<(), Box<dyn Error + Send + Sync>> {
-                    buffer.set_color(ColorSpec::new().set_fg(Some(Color::Rgb(
-                        pixel.red,
-                        pixel.green,
-                        pixel.blue,
-                    ))))?;
-
-                    if i % self.frame_size.x as usize == 0 {
-                        write!(buffer, "\n")?;
+            let start = buffer.as_slice().len();
+
+            if renderer.synchronized_output {
+                write!(buffer, "{BEGIN_SYNCHRONIZED_UPDATE}")?;
+            }
+            // Every frame is drawn from the top-left corner, so nothing depends on where the last one left the cursor.
+            write!(buffer, "{}", termion::cursor::Goto(1, 1))?;
+
+            self.load_frame_buffer(renderer, buffer)?;
+
+            if renderer.synchronized_output {
+                write!(buffer, "{END_SYNCHRONIZED_UPDATE}")?;
+            }
+
+            renderer.stats.record_frame(buffer.as_slice().len() - start);
+
+            Ok(())
+        }
+
+        /// Function that loads a buffer with the frame drawn with the mode of the renderer.
+        fn load_frame_buffer(
+            &self,
+            renderer: &mut Renderer,
+            buffer: &mut Buffer,
+        ) -> Result<(), Box<dyn Error + Send + Sync>> {
+            // Braille and mosaic cells take their colors from several pixels, dithering them would only add noise.
+            let dithered = match renderer.mode {
+                RenderMode::Ascii(_) | RenderMode::HalfBlock => renderer.colors.dither_frame(self),
+                _ => None,
+            };
+            let frame = dithered.as_ref().unwrap_or(self);
+            let depth = renderer.colors.depth;
+
+            let mut grid = match &renderer.mode {
+                RenderMode::Ascii(encoding) => frame.ascii_grid(encoding, depth),
+                RenderMode::HalfBlock => half_block::render(frame, depth),
+                RenderMode::Mosaic(glyphs) => mosaic::render(frame, *glyphs, depth),
+                RenderMode::Braille(fill) => braille::render(frame, *fill, depth),
+                RenderMode::Sixel(colors) => {
+                    return self.load_image_buffer(
+                        sixel::encode(self.into_image().buffer(), *colors),
+                        buffer,
+                    );
+                }
+                RenderMode::Kitty(payload) => {
+                    let image = self.into_image();
+                    let placement = terminal_size().ok().map(|(columns, rows)| {
+                        renderer.cell_placement(image.buffer().dimensions(), columns, rows)
+                    });
+                    if let Some(((column, row), _)) = placement {
+                        write!(buffer, "{}", termion::cursor::Goto(column + 1, row + 1))?;
                     }
 
-                    let char_to_print = encoding.from_greyscale_value8(pixel.grey_scale);
+                    return self.load_image_buffer(
+                        kitty::encode(image.buffer(), *payload, placement.map(|(_, cells)| cells))?,
+                        buffer,
+                    );
+                }
+                RenderMode::Iterm(format) => {
+                    return self.load_image_buffer(
+                        iterm::encode(self.into_image().buffer(), *format)?,
+                        buffer,
+                    );
+                }
+            };
 
-                    write!(buffer, "{char_to_print}{char_to_print}")?;
+            grid.simplify(renderer.colors.snap);
+            renderer.painter.paint(grid, buffer)
+        }
 
-                    Ok(())
-                },
-            )?;
+        /// Function that loads a buffer with an image already encoded for a graphics protocol.
+        fn load_image_buffer(
+            &self,
+            image: Vec<u8>,
+            buffer: &mut Buffer,
+        ) -> Result<(), Box<dyn Error + Send + Sync>> {
+            buffer.write_all(&image)?;
 
             Ok(())
         }
 
+        /// Function that returns the cells that draw each pixel as two characters picked by brightness from the
+        /// encoding.
+        fn ascii_grid(&self, encoding: &AsciiEncoding, depth: ColorDepth) -> CellGrid {
+            CellGrid::from_fn(
+                self.frame_size.x as usize * 2,
+                self.frame_size.y as usize,
+                |column, row| match self.pixel(column / 2, row) {
+                    Some(pixel) => Cell {
+                        glyph: encoding.from_greyscale_value8(pixel.grey_scale),
+                        foreground: Some(depth.color(pixel.red, pixel.green, pixel.blue)),
+                        background: None,
+                    },
+                    None => Cell::default(),
+                },
+            )
+        }
+
+        /// Function that returns the pixel at (x, y), if it is inside the frame.
+        pub fn pixel(&self, x: usize, y: usize) -> Option<&Pixel> {
+            match x < self.frame_size.x as usize {
+                true => self.pixels.get(y * self.frame_size.x as usize + x),
+                false => None,
+            }
+        }
+
         /// Function that converts a frame into an image to facilitate usage of `image` crate's effects.
         pub fn into_image(&self) -> Image {
             let mut image = Image::new(self.frame_size.x as u32, self.frame_size.y as u32);
@@ -312,26 +493,19 @@ pub mod frame {
             Self(ImageBuffer::new(x, y))
         }
 
-        /// Function that resizes an image to the size of the terminal. The x-axis coordinate is divided by 2 because
-        /// for each pixel two characters are drawn in the terminal.
-        pub fn image_to_terminal_size(self) -> (Self, u16, u16) {
+        /// Function that resizes an image to the size of the terminal. How many pixels fit in each cell depends
+        /// on the render mode (e.g. in ASCII mode each pixel is drawn as two characters) and the aspect ratio is
+        /// kept unless the renderer stretches frames. Images the terminal scales itself are kept as they are.
+        pub fn image_to_terminal_size(self, renderer: &Renderer) -> (Self, u16, u16) {
             match terminal_size() {
-                Ok((x, y)) => (
-                    Self(image::imageops::resize(
-                        &self.0,
-                        x as u32 / 2,
-                        y as u32,
-                        FILTER,
-                    )),
-                    x / 2,
-                    y,
-                ),
-                Err(_) => {
-                    let (x, y) = (
-                        self.0.width().clone() as u16,
-                        self.0.height().clone() as u16,
-                    );
-                    (self, x / 2, y)
+                Ok((columns, rows)) if !renderer.terminal_scales_images() => {
+                    let scaled = renderer.scale_to_terminal(&self.0, columns, rows);
+                    let (x, y) = (scaled.width() as u16, scaled.height() as u16);
+                    (Self(scaled), x, y)
+                }
+                _ => {
+                    let (x, y) = (self.0.width() as u16, self.0.height() as u16);
+                    (self, x, y)
                 }
             }
         }
@@ -0,0 +1,102 @@
+//! Module that loads still images (e.g. PNG or JPEG) or animated GIFs and converts them into a feed.
+
+use crate::feed::{Feed, FeedSource};
+use crate::video_file::Pacer;
+use image::codecs::gif::GifDecoder;
+use image::{AnimationDecoder, DynamicImage, ImageBuffer, Rgb, RgbImage};
+use std::{error::Error, fs::File, io::BufReader, path::Path, time::Duration};
+
+/// Struct that represents an image file shown as a feed. A still image is a single frame repeated forever
+/// and an animated GIF loops through its frames honoring their delays.
+pub struct ImageFile {
+    frames: Vec<(RgbImage, Duration)>,
+    current: usize,
+    pacer: Pacer,
+}
+
+impl ImageFile {
+    /// How often a still image is repeated, so consumers are not flooded with identical frames.
+    const STILL_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+    /// Browsers treat GIF delays at or below this value as unset and use `STILL_FRAME_DELAY` instead.
+    const MIN_GIF_DELAY: Duration = Duration::from_millis(10);
+
+    /// Function that opens an image file. Files with the `.gif` extension are decoded as animations and
+    /// every other format supported by the `image` crate is loaded as a still image.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let is_gif = path
+            .as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("gif"));
+
+        let frames = match is_gif {
+            true => Self::decode_gif(path)?,
+            false => vec![(image::open(path)?.into_rgb8(), Self::STILL_FRAME_DELAY)],
+        };
+
+        Self::from_frames(frames)
+    }
+
+    /// Function that creates an image feed from already decoded frames and the time each one stays on screen.
+    pub fn from_frames(
+        frames: Vec<(RgbImage, Duration)>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        if frames.is_empty() {
+            return Err("The image has no frames.".into());
+        }
+
+        Ok(Self {
+            frames,
+            current: 0,
+            pacer: Pacer::new(),
+        })
+    }
+
+    /// Function that decodes every frame of an animated GIF together with its delay.
+    fn decode_gif(
+        path: impl AsRef<Path>,
+    ) -> Result<Vec<(RgbImage, Duration)>, Box<dyn Error + Send + Sync>> {
+        let decoder = GifDecoder::new(BufReader::new(File::open(path)?))?;
+
+        Ok(decoder
+            .into_frames()
+            .collect_frames()?
+            .into_iter()
+            .map(|frame| {
+                let (numerator, denominator) = frame.delay().numer_denom_ms();
+                let delay = Duration::from_secs_f64(numerator as f64 / denominator as f64 / 1000.);
+                let delay = match delay <= Self::MIN_GIF_DELAY {
+                    true => Self::STILL_FRAME_DELAY,
+                    false => delay,
+                };
+
+                (
+                    DynamicImage::ImageRgba8(frame.into_buffer()).into_rgb8(),
+                    delay,
+                )
+            })
+            .collect())
+    }
+
+    /// Function that returns the number of frames in the image.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+impl Feed for ImageFile {}
+
+impl FeedSource for ImageFile {
+    fn get_frame_rgb(
+        &mut self,
+    ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, Box<dyn Error + Send + Sync>> {
+        let (frame, delay) = &self.frames[self.current];
+        self.pacer.wait(*delay);
+
+        let frame = frame.clone();
+        self.current = (self.current + 1) % self.frames.len();
+
+        Ok(frame)
+    }
+}
@@ -1,9 +1,21 @@
 use ::image::imageops::FilterType;
 
+pub mod capabilities;
+pub mod capture;
+pub mod composite;
+pub mod config;
 pub mod feed;
+pub mod image_file;
+pub mod raw_input;
+pub mod registry;
+pub mod render;
 pub mod screen_capture;
 pub mod stream;
+pub mod switch;
+pub mod test_pattern;
+pub mod video_file;
 pub mod webcam;
 pub mod window;
+pub mod window_capture;
 
 pub const FILTER: FilterType = FilterType::Nearest;
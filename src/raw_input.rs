@@ -0,0 +1,117 @@
+//! Module that reads fixed-size raw `rgb24` frames from stdin or a named pipe and converts them into a feed.
+
+use crate::feed::{Feed, FeedSource};
+use crate::video_file::{raw_frame_bytes, read_raw_rgb};
+use image::{ImageBuffer, Rgb};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+/// Struct that represents a feed of raw frames piped in by another program (e.g. `ffmpeg -f rawvideo -pix_fmt rgb24 -`).
+/// The writer is responsible for the pacing. When it closes, the end flag is raised instead of returning an error.
+pub struct RawInput {
+    reader: BufReader<Box<dyn Read + Send>>,
+    width: u32,
+    height: u32,
+    end_flag: Arc<AtomicBool>,
+    last_frame: Option<ImageBuffer<Rgb<u8>, Vec<u8>>>,
+}
+
+impl RawInput {
+    /// Function that creates a raw feed from any reader with the frame geometry given up front.
+    pub fn from_reader(
+        reader: Box<dyn Read + Send>,
+        width: u32,
+        height: u32,
+        end_flag: Arc<AtomicBool>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        if width == 0 || height == 0 {
+            return Err("Raw input needs a non-zero frame size.".into());
+        }
+        raw_frame_bytes(width, height)?;
+
+        Ok(Self {
+            reader: BufReader::new(reader),
+            width,
+            height,
+            end_flag,
+            last_frame: None,
+        })
+    }
+
+    /// Function that creates a raw feed that reads from stdin.
+    pub fn stdin(
+        width: u32,
+        height: u32,
+        end_flag: Arc<AtomicBool>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Self::from_reader(Box::new(std::io::stdin()), width, height, end_flag)
+    }
+
+    /// Function that creates a raw feed that reads from a named pipe (FIFO). Opening blocks until a writer connects.
+    pub fn fifo(
+        path: impl AsRef<Path>,
+        width: u32,
+        height: u32,
+        end_flag: Arc<AtomicBool>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Self::from_reader(Box::new(File::open(path)?), width, height, end_flag)
+    }
+}
+
+impl Feed for RawInput {}
+
+impl FeedSource for RawInput {
+    fn get_frame_rgb(
+        &mut self,
+    ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, Box<dyn Error + Send + Sync>> {
+        match read_raw_rgb(&mut self.reader, self.width, self.height)? {
+            Some(frame) => {
+                self.last_frame = Some(frame.clone());
+                Ok(frame)
+            }
+            None => {
+                self.end_flag.store(true, Ordering::Release);
+                Ok(self
+                    .last_frame
+                    .clone()
+                    .unwrap_or_else(|| ImageBuffer::new(self.width, self.height)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reader(bytes: &[u8]) -> Box<dyn Read + Send> {
+        Box::new(std::io::Cursor::new(bytes.to_vec()))
+    }
+
+    #[test]
+    fn rejects_oversized_geometry() {
+        let end_flag = Arc::new(AtomicBool::new(false));
+
+        assert!(RawInput::from_reader(reader(&[]), 40000, 40000, end_flag.clone()).is_err());
+        assert!(RawInput::from_reader(reader(&[]), 0, 10, end_flag).is_err());
+    }
+
+    #[test]
+    fn raises_the_end_flag_when_the_input_ends() {
+        let end_flag = Arc::new(AtomicBool::new(false));
+        let mut input =
+            RawInput::from_reader(reader(&[1, 2, 3, 4]), 1, 1, end_flag.clone()).unwrap();
+
+        assert_eq!(input.get_frame_rgb().unwrap().as_raw(), &vec![1, 2, 3]);
+        assert_eq!(input.get_frame_rgb().unwrap().as_raw(), &vec![1, 2, 3]);
+        assert!(end_flag.load(Ordering::Acquire));
+    }
+}
@@ -0,0 +1,593 @@
+//! Module that implements the registry used to create feed sources by name at runtime.
+
+use crate::composite::{InsetLayout, PictureInPicture};
+use crate::config::{FeedConfig, parse_geometry, take_option};
+use crate::feed::{Feed, FeedConstructor, FeedSource};
+use crate::image_file::ImageFile;
+use crate::raw_input::RawInput;
+use crate::screen_capture::{Screen, ScreenArea};
+use crate::test_pattern::TestPattern;
+use crate::video_file::VideoFile;
+use crate::webcam::{CameraSelector, WebCam};
+use crate::window_capture::{WindowCapture, WindowSelector};
+use image::Rgb;
+use std::{
+    error::Error,
+    fmt,
+    str::FromStr,
+    sync::{Arc, atomic::AtomicBool},
+};
+
+/// Struct that represents everything a source may need to be created.
+pub struct SourceRequest<'a> {
+    /// Source-specific argument (e.g. the path of a file or the title of a window).
+    pub argument: Option<&'a str>,
+    pub config: &'a FeedConfig,
+    /// Flag raised by sources that can end on their own (e.g. a file that isn't looped).
+    pub end_flag: &'a Arc<AtomicBool>,
+}
+
+/// Function that creates a boxed feed source from a request. The registry is given so sources made of others
+/// (e.g. `pip`) can create them by name.
+pub type SourceConstructor = fn(
+    &SourceRegistry,
+    &SourceRequest,
+) -> Result<Box<dyn FeedSource>, Box<dyn Error + Send + Sync>>;
+
+/// Function that lists what the argument of a source can select (e.g. the available monitors), one per line.
+pub type SourceLister = fn() -> Result<Vec<String>, Box<dyn Error + Send + Sync>>;
+
+/// Struct that represents a source that can be created by name.
+pub struct SourceEntry {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub default_config: fn() -> FeedConfig,
+    pub constructor: SourceConstructor,
+    /// Lister of the devices the source can pick from, for sources that select one.
+    pub lister: Option<SourceLister>,
+}
+
+/// Struct that represents a source chosen on the command line, written as `NAME` or `NAME:ARGUMENT`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceSpec {
+    pub name: String,
+    pub argument: Option<String>,
+}
+
+impl Default for SourceSpec {
+    fn default() -> Self {
+        Self {
+            name: "webcam".to_string(),
+            argument: None,
+        }
+    }
+}
+
+impl FromStr for SourceSpec {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, argument) = match s.split_once(':') {
+            Some((name, argument)) => (name, Some(argument.to_string())),
+            None => (s, None),
+        };
+
+        if name.is_empty() {
+            return Err("The source name is empty.".into());
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            argument,
+        })
+    }
+}
+
+impl fmt::Display for SourceSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.argument {
+            Some(argument) => write!(f, "{}:{argument}", self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+impl SourceSpec {
+    /// Function that removes `--source SPEC` from the arguments and parses it.
+    pub fn take_from_args(
+        args: &mut Vec<String>,
+    ) -> Result<Option<Self>, Box<dyn Error + Send + Sync>> {
+        match take_option(args, "--source")? {
+            Some(spec) => Ok(Some(spec.parse()?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Struct that represents the argument of the file source, written as `PATH[@WIDTHxHEIGHT@FPS][@loop]`. Raw
+/// `rgb24` files need the frame size and frame rate since they have no header.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileArgument {
+    pub path: String,
+    pub raw_geometry: Option<(u32, u32, f64)>,
+    pub looping: bool,
+}
+
+impl FromStr for FileArgument {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('@');
+        let path = parts.next().unwrap_or_default().to_string();
+        if path.is_empty() {
+            return Err("The file source needs a path.".into());
+        }
+
+        let (mut size, mut fps, mut looping) = (None, None, false);
+        for part in parts {
+            match part {
+                "loop" => looping = true,
+                _ if part.contains(['x', 'X']) => size = Some(parse_geometry(part)?),
+                _ => {
+                    fps = Some(part.parse::<f64>().map_err(|_| {
+                        format!("Invalid file option `{part}`, expected WIDTHxHEIGHT, FPS or loop.")
+                    })?)
+                }
+            }
+        }
+
+        let raw_geometry = match (size, fps) {
+            (Some((width, height)), Some(fps)) => Some((width, height, fps)),
+            (None, None) => None,
+            _ => return Err("Raw video files need both the frame size and the frame rate.".into()),
+        };
+
+        Ok(Self {
+            path,
+            raw_geometry,
+            looping,
+        })
+    }
+}
+
+/// Struct that represents the argument of the picture-in-picture source, written as `[CORNER][@KEY=VALUE]...` with
+/// the keys `scale`, `margin`, `border`, `border-color` (as `RRGGBB`), `main` and `inset` (as source names).
+#[derive(Clone, Debug, PartialEq)]
+pub struct PipArgument {
+    pub layout: InsetLayout,
+    pub main: String,
+    pub inset: String,
+}
+
+impl Default for PipArgument {
+    fn default() -> Self {
+        Self {
+            layout: InsetLayout::default(),
+            main: "screen".to_string(),
+            inset: "webcam".to_string(),
+        }
+    }
+}
+
+impl FromStr for PipArgument {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut argument = Self::default();
+        let mut parts = s.split('@');
+        if let Some(corner) = parts.next().filter(|corner| !corner.is_empty()) {
+            argument.layout.corner = corner.parse()?;
+        }
+
+        for part in parts {
+            let (key, value) = part.split_once('=').ok_or_else(|| {
+                format!("Invalid picture-in-picture option `{part}`, expected KEY=VALUE.")
+            })?;
+            let invalid = || format!("Invalid value `{value}` for the picture-in-picture `{key}`.");
+
+            match key {
+                "scale" => {
+                    argument.layout.scale = value
+                        .parse()
+                        .ok()
+                        .filter(|scale| *scale > 0. && *scale <= 1.)
+                        .ok_or_else(|| format!("{} Expected a fraction from 0 to 1.", invalid()))?
+                }
+                "margin" => argument.layout.margin = value.parse().map_err(|_| invalid())?,
+                "border" => argument.layout.border = value.parse().map_err(|_| invalid())?,
+                "border-color" => {
+                    argument.layout.border_color =
+                        parse_rgb(value).ok_or_else(|| format!("{} Expected RRGGBB.", invalid()))?
+                }
+                "main" => argument.main = value.to_string(),
+                "inset" => argument.inset = value.to_string(),
+                _ => {
+                    return Err(format!(
+                        "Unknown picture-in-picture option `{key}`, expected scale, margin, border, border-color, main or inset."
+                    )
+                    .into());
+                }
+            }
+        }
+
+        Ok(argument)
+    }
+}
+
+/// Function that parses a color written as six hexadecimal digits, `RRGGBB`.
+fn parse_rgb(s: &str) -> Option<Rgb<u8>> {
+    if s.len() != 6 || !s.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&s[i..i + 2], 16).ok();
+
+    Some(Rgb([channel(0)?, channel(2)?, channel(4)?]))
+}
+
+/// Struct that represents the collection of sources that can be created by name.
+pub struct SourceRegistry {
+    entries: Vec<SourceEntry>,
+}
+
+impl Default for SourceRegistry {
+    /// Function that creates a registry with every source of the crate.
+    fn default() -> Self {
+        let mut registry = Self::new();
+
+        registry.register(SourceEntry {
+            name: "webcam",
+            usage: "webcam[:INDEX|NAME]",
+            default_config: WebCam::default_config,
+            constructor: |_, request| match request.argument {
+                Some(selector) => Ok(Box::new(WebCam::open(
+                    &selector.parse::<CameraSelector>()?,
+                    &WebCam::default_preferences(),
+                )?)),
+                None => Ok(Box::new(WebCam::new(request.config)?)),
+            },
+            lister: Some(|| {
+                Ok(WebCam::list_cameras()?
+                    .into_iter()
+                    .map(|camera| {
+                        format!("{}: {} ({})", camera.index, camera.name, camera.description)
+                    })
+                    .collect())
+            }),
+        });
+        registry.register(SourceEntry {
+            name: "screen",
+            usage: "screen[:all|INDEX|NAME][@WIDTHxHEIGHT+X+Y]",
+            default_config: Screen::default_config,
+            constructor: |_, request| match request.argument {
+                Some(area) => Ok(Box::new(Screen::with_area(area.parse::<ScreenArea>()?)?)),
+                None => Ok(Box::new(Screen::new(request.config)?)),
+            },
+            lister: Some(|| {
+                Ok(Screen::list_monitors()?
+                    .into_iter()
+                    .map(|monitor| {
+                        format!(
+                            "{}: {} {}x{} at {},{}{}",
+                            monitor.index,
+                            monitor.name,
+                            monitor.width,
+                            monitor.height,
+                            monitor.x,
+                            monitor.y,
+                            match monitor.is_primary {
+                                true => " (primary)",
+                                false => "",
+                            }
+                        )
+                    })
+                    .collect())
+            }),
+        });
+        registry.register(SourceEntry {
+            name: "test-pattern",
+            usage: "test-pattern[:bars|gradient][@SEED]",
+            default_config: TestPattern::default_config,
+            constructor: |_, request| match request.argument {
+                Some(pattern) => Ok(Box::new(pattern.parse::<TestPattern>()?)),
+                None => Ok(Box::new(TestPattern::new(request.config)?)),
+            },
+            lister: None,
+        });
+        registry.register(SourceEntry {
+            name: "file",
+            usage: "file:PATH[@WIDTHxHEIGHT@FPS][@loop] (.y4m or raw rgb24 video, animated .gif or still image)",
+            default_config: ImageFile::default_config,
+            constructor: |_, request| {
+                let file = request
+                    .argument
+                    .ok_or("The file source needs a path.")?
+                    .parse::<FileArgument>()?;
+                let is_video = file.raw_geometry.is_some()
+                    || [".y4m", ".rgb", ".raw"]
+                        .iter()
+                        .any(|extension| file.path.to_ascii_lowercase().ends_with(extension));
+
+                match (is_video, file.looping) {
+                    (true, _) => Ok(Box::new(VideoFile::open_path(
+                        &file.path,
+                        file.raw_geometry,
+                        file.looping,
+                        request.end_flag.clone(),
+                    )?)),
+                    (false, false) => Ok(Box::new(ImageFile::open(&file.path)?)),
+                    (false, true) => Err("Images always loop, `@loop` is only for videos.".into()),
+                }
+            },
+            lister: None,
+        });
+        registry.register(SourceEntry {
+            name: "window",
+            usage: "window:TITLE|app=NAME",
+            default_config: WindowCapture::default_config,
+            constructor: |_, request| {
+                let selector = request
+                    .argument
+                    .ok_or("The window source needs a title or an application name.")?;
+                Ok(Box::new(WindowCapture::with_selector(
+                    selector.parse::<WindowSelector>()?,
+                )?))
+            },
+            lister: Some(|| {
+                Ok(WindowCapture::list_windows()?
+                    .into_iter()
+                    .map(|window| format!("{} ({})", window.title, window.app_name))
+                    .collect())
+            }),
+        });
+        registry.register(SourceEntry {
+            name: "pip",
+            usage: "pip[:CORNER][@scale=FRACTION][@margin=PIXELS][@border=PIXELS][@border-color=RRGGBB][@main=SOURCE][@inset=SOURCE] (a source with another one inset, the screen and the webcam by default)",
+            default_config: PictureInPicture::<Screen, WebCam>::default_config,
+            constructor: |registry, request| {
+                let pip = request
+                    .argument
+                    .map(str::parse::<PipArgument>)
+                    .transpose()?
+                    .unwrap_or_default();
+                let part = |name: &str| {
+                    registry.create(
+                        name,
+                        &SourceRequest {
+                            argument: None,
+                            ..*request
+                        },
+                    )
+                };
+
+                Ok(Box::new(PictureInPicture::with_sources(
+                    part(&pip.main)?,
+                    part(&pip.inset)?,
+                    pip.layout,
+                )))
+            },
+            lister: None,
+        });
+        registry.register(SourceEntry {
+            name: "stdin",
+            usage: "stdin:WIDTHxHEIGHT",
+            default_config: RawInput::default_config,
+            constructor: |_, request| {
+                let geometry = request
+                    .argument
+                    .ok_or("The stdin source needs a frame size.")?;
+                let (width, height) = parse_geometry(geometry)?;
+                Ok(Box::new(RawInput::stdin(
+                    width,
+                    height,
+                    request.end_flag.clone(),
+                )?))
+            },
+            lister: None,
+        });
+
+        registry
+    }
+}
+
+impl SourceRegistry {
+    /// Function that creates an empty registry.
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Function that adds a source to the registry, replacing any source with the same name.
+    pub fn register(&mut self, entry: SourceEntry) {
+        self.entries.retain(|e| e.name != entry.name);
+        self.entries.push(entry);
+    }
+
+    /// Function that returns the sources in the registry.
+    pub fn entries(&self) -> &[SourceEntry] {
+        &self.entries
+    }
+
+    /// Function that returns the source with the given name.
+    pub fn get(&self, name: &str) -> Result<&SourceEntry, Box<dyn Error + Send + Sync>> {
+        match self.entries.iter().find(|e| e.name == name) {
+            Some(entry) => Ok(entry),
+            None => Err(format!(
+                "Unknown source `{name}`, expected one of: {}.",
+                self.entries
+                    .iter()
+                    .map(|e| e.usage)
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            )
+            .into()),
+        }
+    }
+
+    /// Function that describes every source with the devices it can pick from, one line each, so they can be shown
+    /// to choose the argument of a source.
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        for entry in &self.entries {
+            lines.push(entry.usage.to_string());
+
+            match entry.lister.map(|lister| lister()) {
+                Some(Ok(choices)) if choices.is_empty() => {
+                    lines.push("  None available.".to_string())
+                }
+                Some(Ok(choices)) => {
+                    lines.extend(choices.iter().map(|choice| format!("  {choice}")))
+                }
+                Some(Err(error)) => lines.push(format!("  Couldn't be listed: {error}")),
+                None => {}
+            }
+        }
+
+        lines
+    }
+
+    /// Function that creates the source with the given name.
+    pub fn create(
+        &self,
+        name: &str,
+        request: &SourceRequest,
+    ) -> Result<Box<dyn FeedSource>, Box<dyn Error + Send + Sync>> {
+        (self.get(name)?.constructor)(self, request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::composite::Corner;
+
+    #[test]
+    fn describes_sources_with_their_choices() {
+        let entry = |name, lister| SourceEntry {
+            name,
+            usage: name,
+            default_config: TestPattern::default_config,
+            constructor: |_, _| Err("Not used.".into()),
+            lister,
+        };
+
+        let mut registry = SourceRegistry::new();
+        registry.register(entry("plain", None));
+        registry.register(entry("devices", Some(|| Ok(vec!["0: first".to_string()]))));
+        registry.register(entry("empty", Some(|| Ok(Vec::new()))));
+        registry.register(entry("broken", Some(|| Err("No backend.".into()))));
+
+        assert_eq!(
+            registry.describe(),
+            [
+                "plain",
+                "devices",
+                "  0: first",
+                "empty",
+                "  None available.",
+                "broken",
+                "  Couldn't be listed: No backend."
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_source_specs_as_parsed() {
+        ["webcam", "screen:1@640x480+0+0", "window:title=a:b"]
+            .iter()
+            .for_each(|spec| assert_eq!(spec.parse::<SourceSpec>().unwrap().to_string(), *spec));
+    }
+
+    #[test]
+    fn parses_pip_arguments() {
+        assert_eq!("".parse::<PipArgument>().unwrap(), PipArgument::default());
+        assert_eq!(
+            "top-left@scale=0.5@margin=0@border=4@border-color=ff8000@main=test-pattern@inset=screen"
+                .parse::<PipArgument>()
+                .unwrap(),
+            PipArgument {
+                layout: InsetLayout {
+                    corner: Corner::TopLeft,
+                    scale: 0.5,
+                    margin: 0,
+                    border: 4,
+                    border_color: Rgb([255, 128, 0]),
+                },
+                main: "test-pattern".to_string(),
+                inset: "screen".to_string(),
+            }
+        );
+        assert_eq!(
+            "@border=0".parse::<PipArgument>().unwrap().layout.corner,
+            Corner::BottomRight
+        );
+
+        [
+            "middle",
+            "top-left@0.5",
+            "@scale=0",
+            "@scale=2",
+            "@border=-1",
+            "@border-color=fff",
+            "@border-color=gg0000",
+            "@size=2",
+        ]
+        .iter()
+        .for_each(|argument| assert!(argument.parse::<PipArgument>().is_err(), "{argument}"));
+    }
+
+    #[test]
+    fn creates_pip_parts_by_name() {
+        let registry = SourceRegistry::default();
+        let config = TestPattern::default_config();
+        let end_flag = Arc::new(AtomicBool::new(false));
+        let request = |argument| SourceRequest {
+            argument: Some(argument),
+            config: &config,
+            end_flag: &end_flag,
+        };
+
+        let mut pip = registry
+            .create(
+                "pip",
+                &request("top-left@main=test-pattern@inset=test-pattern@border=0"),
+            )
+            .unwrap();
+        assert!(pip.get_frame_rgb().is_ok());
+
+        let error = registry
+            .create("pip", &request("@main=test-pattern@inset=nothing"))
+            .err()
+            .unwrap();
+        assert!(error.to_string().starts_with("Unknown source `nothing`"));
+    }
+
+    #[test]
+    fn parses_file_arguments() {
+        assert_eq!(
+            "clip.y4m".parse::<FileArgument>().unwrap(),
+            FileArgument {
+                path: "clip.y4m".to_string(),
+                raw_geometry: None,
+                looping: false,
+            }
+        );
+        assert_eq!(
+            "clip.rgb@320x240@25@loop".parse::<FileArgument>().unwrap(),
+            FileArgument {
+                path: "clip.rgb".to_string(),
+                raw_geometry: Some((320, 240, 25.)),
+                looping: true,
+            }
+        );
+        assert!("clip.y4m@loop".parse::<FileArgument>().unwrap().looping);
+    }
+
+    #[test]
+    fn rejects_incomplete_raw_geometry() {
+        ["clip.rgb@320x240", "clip.rgb@25", "@loop", "clip.rgb@fast"]
+            .iter()
+            .for_each(|argument| assert!(argument.parse::<FileArgument>().is_err(), "{argument}"));
+    }
+}
@@ -0,0 +1,341 @@
+//! Module that implements the different ways a frame can be drawn in the terminal.
+
+pub mod ansi;
+pub mod braille;
+pub mod color;
+pub mod grid;
+pub mod half_block;
+pub mod iterm;
+pub mod kitty;
+pub mod mosaic;
+pub mod pacing;
+pub mod scaling;
+pub mod sixel;
+pub mod stats;
+
+use crate::capabilities::Capabilities;
+use crate::config::{parse_geometry, take_option};
+use crate::feed::frame::AsciiEncoding;
+use crate::render::braille::DotFill;
+use crate::render::color::ColorOptions;
+use crate::render::grid::GridPainter;
+use crate::render::iterm::ItermFormat;
+use crate::render::kitty::KittyPayload;
+use crate::render::mosaic::Mosaic;
+use crate::render::pacing::FramePacer;
+use crate::render::scaling::Scaling;
+use crate::render::stats::RenderStats;
+use image::RgbImage;
+use std::error::Error;
+use std::sync::Arc;
+
+/// Sequence that makes the terminal hold what is written until the end of the update (DEC private mode 2026).
+pub const BEGIN_SYNCHRONIZED_UPDATE: &str = "\x1b[?2026h";
+
+/// Sequence that ends a synchronized update, the terminal then shows everything written since it began.
+pub const END_SYNCHRONIZED_UPDATE: &str = "\x1b[?2026l";
+
+/// Size, in pixels, assumed for a cell when the terminal doesn't report it.
+pub const DEFAULT_CELL_PIXEL_SIZE: (u32, u32) = (10, 20);
+
+/// Characters used to encode 6 bits each in base64.
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Function that encodes bytes as padded base64, used by the image protocols to send pixels as text.
+pub fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    bytes.chunks(3).for_each(|chunk| {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | (byte as u32) << (16 - 8 * i)
+        });
+
+        (0..4).for_each(|i| match i <= chunk.len() {
+            true => out.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3F) as usize] as char),
+            false => out.push('='),
+        });
+    });
+
+    out
+}
+
+/// Enum that represents how frames are drawn in the terminal.
+pub enum RenderMode {
+    /// Each pixel is drawn as two characters picked by brightness from the encoding.
+    Ascii(AsciiEncoding),
+    /// Each cell is drawn as `▀` and holds two vertically stacked pixels, the top one in the foreground
+    /// color and the bottom one in the background color.
+    HalfBlock,
+    /// Each cell is drawn as a braille character holding a block of 2x4 pixels, one dot per pixel, colored with
+    /// the average color of the block.
+    Braille(DotFill),
+    /// Each cell is drawn as the quadrant (2x2 pixels) or sextant (2x3 pixels) character that best splits the
+    /// block into a foreground and a background color.
+    Mosaic(Mosaic),
+    /// Each frame is drawn as a sixel image quantized to a palette of the given number of colors, for terminals
+    /// that can show real pixels.
+    Sixel(usize),
+    /// Each frame is sent with the kitty graphics protocol, replacing the previous frame in place.
+    Kitty(KittyPayload),
+    /// Each frame is sent as a PNG or JPEG file with the iTerm2 inline image protocol.
+    Iterm(ItermFormat),
+}
+
+impl RenderMode {
+    /// Names accepted by `RenderMode::from_name`.
+    pub const NAMES: &[&str] = &[
+        "ascii",
+        "half-block",
+        "quadrant",
+        "sextant",
+        "braille",
+        "braille-threshold",
+        "sixel",
+        "kitty",
+        "kitty-png",
+        "iterm",
+        "iterm-jpeg",
+    ];
+
+    /// Function that creates a render mode from its name. The encoding is only used by the ASCII mode.
+    pub fn from_name(
+        name: &str,
+        encoding: AsciiEncoding,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        match name {
+            "ascii" => Ok(Self::Ascii(encoding)),
+            "half-block" => Ok(Self::HalfBlock),
+            "quadrant" => Ok(Self::Mosaic(Mosaic::Quadrant)),
+            "sextant" => Ok(Self::Mosaic(Mosaic::Sextant)),
+            "braille" => Ok(Self::Braille(DotFill::Ordered)),
+            "braille-threshold" => Ok(Self::Braille(DotFill::Threshold)),
+            "sixel" => Ok(Self::Sixel(sixel::DEFAULT_COLORS)),
+            "kitty" => Ok(Self::Kitty(KittyPayload::Rgb)),
+            "kitty-png" => Ok(Self::Kitty(KittyPayload::Png)),
+            "iterm" => Ok(Self::Iterm(ItermFormat::Png)),
+            "iterm-jpeg" => Ok(Self::Iterm(ItermFormat::Jpeg(iterm::DEFAULT_JPEG_QUALITY))),
+            _ => Err(format!(
+                "Unknown render mode `{name}`, expected one of: {}.",
+                Self::NAMES.join(", ")
+            )
+            .into()),
+        }
+    }
+
+    /// Function that returns whether the mode sends real images instead of drawing with characters.
+    pub fn is_image(&self) -> bool {
+        matches!(self, Self::Sixel(_) | Self::Kitty(_) | Self::Iterm(_))
+    }
+
+    /// Function that returns how many pixels of the frame are drawn in each cell, horizontally and vertically.
+    pub fn pixels_per_cell(&self, cell_pixel_size: (u32, u32)) -> (f64, f64) {
+        match self {
+            Self::Ascii(_) => (0.5, 1.),
+            Self::HalfBlock => (1., 2.),
+            Self::Mosaic(mosaic) => {
+                let (cell_width, cell_height) = mosaic.cell_size();
+                (cell_width as f64, cell_height as f64)
+            }
+            Self::Braille(_) => (braille::CELL_SIZE.0 as f64, braille::CELL_SIZE.1 as f64),
+            Self::Sixel(_) | Self::Kitty(_) | Self::Iterm(_) => {
+                (cell_pixel_size.0 as f64, cell_pixel_size.1 as f64)
+            }
+        }
+    }
+
+    /// Function that returns the width of a pixel of the frame divided by its height, as drawn in the terminal.
+    pub fn pixel_aspect(&self, cell_pixel_size: (u32, u32)) -> f64 {
+        let (x, y) = self.pixels_per_cell(cell_pixel_size);
+        (cell_pixel_size.0 as f64 / x) / (cell_pixel_size.1.max(1) as f64 / y)
+    }
+
+    /// Function that returns the size, in pixels, of a frame that fills a terminal with the given number of cells.
+    pub fn frame_size(&self, columns: u16, rows: u16, cell_pixel_size: (u32, u32)) -> (u32, u32) {
+        let (x, y) = self.pixels_per_cell(cell_pixel_size);
+        // The last row is left empty so drawing an image doesn't scroll the terminal.
+        let rows = match self.is_image() {
+            true => rows.saturating_sub(1),
+            false => rows,
+        };
+
+        ((columns as f64 * x) as u32, (rows as f64 * y) as u32)
+    }
+}
+
+/// Struct that represents everything that decides how frames are drawn in the terminal.
+pub struct Renderer {
+    pub mode: RenderMode,
+    /// Colors used by the modes that draw with characters.
+    pub colors: ColorOptions,
+    pub scaling: Scaling,
+    /// Size, in pixels, of a cell of the terminal, used to keep the aspect ratio of the frames.
+    pub cell_pixel_size: (u32, u32),
+    /// Cells drawn for the last frame, so the next one only redraws the ones that changed.
+    pub painter: GridPainter,
+    /// Time printing frames takes, so frames that come while the terminal is still busy are dropped.
+    pub pacer: FramePacer,
+    /// Counters of what was drawn, shared so they can be read once the feed ends.
+    pub stats: Arc<RenderStats>,
+    /// Whether each frame is written as a synchronized update, so the terminal never shows half of it. Only
+    /// enabled for terminals that report support for it.
+    pub synchronized_output: bool,
+}
+
+impl Renderer {
+    /// Function that creates a renderer that fits frames to the terminal assuming the default cell size.
+    pub fn new(mode: RenderMode, colors: ColorOptions) -> Self {
+        Self {
+            mode,
+            colors,
+            scaling: Scaling::default(),
+            cell_pixel_size: DEFAULT_CELL_PIXEL_SIZE,
+            painter: GridPainter::new(),
+            pacer: FramePacer::new(),
+            stats: Arc::default(),
+            synchronized_output: false,
+        }
+    }
+
+    /// Function that scales an image to the frame that fills a terminal with the given number of cells.
+    pub fn scale_to_terminal(&self, image: &RgbImage, columns: u16, rows: u16) -> RgbImage {
+        let canvas = self.mode.frame_size(columns, rows, self.cell_pixel_size);
+        let pixel_aspect = self.mode.pixel_aspect(self.cell_pixel_size);
+
+        self.scaling.scale(image, canvas, pixel_aspect)
+    }
+
+    /// Function that returns whether frames are sent at their own size and scaled by the terminal, which the kitty
+    /// graphics protocol supports. Filling the terminal crops the frame, so it is still scaled before being sent.
+    pub fn terminal_scales_images(&self) -> bool {
+        matches!(self.mode, RenderMode::Kitty(_)) && self.scaling != Scaling::Fill
+    }
+
+    /// Function that returns where the terminal draws an image of the given size to fit it in a terminal with the
+    /// given number of cells, as the cell of its top left corner and the number of columns and rows it covers.
+    pub fn cell_placement(
+        &self,
+        image: (u32, u32),
+        columns: u16,
+        rows: u16,
+    ) -> ((u16, u16), (u16, u16)) {
+        let canvas = self.mode.frame_size(columns, rows, self.cell_pixel_size);
+        let pixel_aspect = self.mode.pixel_aspect(self.cell_pixel_size);
+        let (width, height) = self.scaling.scaled_size(image, canvas, pixel_aspect);
+        let (cell_width, cell_height) =
+            (self.cell_pixel_size.0.max(1), self.cell_pixel_size.1.max(1));
+
+        let cells = |pixels: u32, cell: u32, available: u32| {
+            (pixels as f64 / cell as f64)
+                .round()
+                .clamp(1., available.max(1) as f64) as u16
+        };
+        let size = (
+            cells(width, cell_width, canvas.0 / cell_width),
+            cells(height, cell_height, canvas.1 / cell_height),
+        );
+        let corner = (
+            ((canvas.0 / cell_width) as u16).saturating_sub(size.0) / 2,
+            ((canvas.1 / cell_height) as u16).saturating_sub(size.1) / 2,
+        );
+
+        (corner, size)
+    }
+
+    /// Function that removes the render options (`--render`, `--colors`, `--dither`, `--scale` and `--cell-size`)
+    /// from the arguments and parses them. Options that aren't given are picked from the capabilities of the
+    /// terminal.
+    pub fn take_from_args(
+        args: &mut Vec<String>,
+        encoding: AsciiEncoding,
+        capabilities: &Capabilities,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mode = match take_option(args, "--render")? {
+            Some(name) => RenderMode::from_name(&name, encoding)?,
+            None => capabilities.render_mode(encoding),
+        };
+
+        let colors = ColorOptions::take_from_args(args, capabilities.color_depth)?;
+
+        let scaling = match take_option(args, "--scale")? {
+            Some(scaling) => scaling.parse()?,
+            None => Scaling::default(),
+        };
+
+        let cell_pixel_size = match take_option(args, "--cell-size")? {
+            Some(size) => match parse_geometry(&size)? {
+                (0, _) | (_, 0) => return Err("The cell size can't be zero.".into()),
+                size => size,
+            },
+            None => capabilities
+                .cell_pixel_size
+                .unwrap_or(DEFAULT_CELL_PIXEL_SIZE),
+        };
+
+        Ok(Self {
+            mode,
+            colors,
+            scaling,
+            cell_pixel_size,
+            painter: GridPainter::new(),
+            pacer: FramePacer::new(),
+            stats: Arc::default(),
+            synchronized_output: capabilities.synchronized_output,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn places_images_scaled_by_the_terminal() {
+        let mut renderer = Renderer::new(
+            RenderMode::Kitty(KittyPayload::Rgb),
+            ColorOptions::default(),
+        );
+        renderer.cell_pixel_size = (10, 20);
+        assert!(renderer.terminal_scales_images());
+
+        // 80x24 cells leave a 800x460 canvas, a 4:3 frame fits its height and is centered horizontally.
+        assert_eq!(
+            renderer.cell_placement((640, 480), 80, 24),
+            ((9, 0), (61, 23))
+        );
+        // A frame as wide as the terminal is centered vertically.
+        assert_eq!(
+            renderer.cell_placement((800, 200), 80, 24),
+            ((0, 6), (80, 10))
+        );
+
+        renderer.scaling = Scaling::Stretch;
+        assert_eq!(
+            renderer.cell_placement((640, 480), 80, 24),
+            ((0, 0), (80, 23))
+        );
+
+        renderer.scaling = Scaling::Fill;
+        assert!(!renderer.terminal_scales_images());
+        renderer.mode = RenderMode::HalfBlock;
+        renderer.scaling = Scaling::Fit;
+        assert!(!renderer.terminal_scales_images());
+    }
+
+    #[test]
+    fn encodes_base64() {
+        [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ]
+        .iter()
+        .for_each(|(bytes, encoded)| assert_eq!(base64(bytes.as_bytes()), *encoded));
+
+        assert_eq!(base64(&[0xFB, 0xFF, 0xBF]), "+/+/");
+    }
+}
@@ -0,0 +1,200 @@
+//! Module that implements the writer of color escape sequences that only sends what changed since the last cell.
+
+use crate::render::grid::Cell;
+use std::io;
+use termcolor::{Color, WriteColor};
+
+/// Glyph of the cells that only show their background.
+const BLANK: char = ' ';
+
+/// Function that returns the SGR parameters that select a color, `None` being the default color. The bright colors
+/// (8 to 15) use their own SGR codes so they also work in terminals without the 256 palette.
+pub fn color_parameters(color: Option<Color>, foreground: bool) -> String {
+    let (base, bright, extended, default) = match foreground {
+        true => (30, 90, 38, 39),
+        false => (40, 100, 48, 49),
+    };
+
+    match color {
+        Some(Color::Black) => format!("{base}"),
+        Some(Color::Red) => format!("{}", base + 1),
+        Some(Color::Green) => format!("{}", base + 2),
+        Some(Color::Yellow) => format!("{}", base + 3),
+        Some(Color::Blue) => format!("{}", base + 4),
+        Some(Color::Magenta) => format!("{}", base + 5),
+        Some(Color::Cyan) => format!("{}", base + 6),
+        Some(Color::White) => format!("{}", base + 7),
+        Some(Color::Ansi256(index @ 8..=15)) => format!("{}", bright + index - 8),
+        Some(Color::Ansi256(index)) => format!("{extended};5;{index}"),
+        Some(Color::Rgb(red, green, blue)) => format!("{extended};2;{red};{green};{blue}"),
+        _ => format!("{default}"),
+    }
+}
+
+/// Struct that represents the colors the terminal is currently drawing with, so only changes are sent. Runs of
+/// cells with the same colors share one escape sequence and blank cells never change the foreground. Outputs that
+/// don't support colors (e.g. when colors are turned off) only get the glyphs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AnsiWriter {
+    foreground: Option<Color>,
+    background: Option<Color>,
+}
+
+impl AnsiWriter {
+    /// Function that creates a writer for a terminal drawing with the default colors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Function that moves the terminal back to the default colors.
+    pub fn reset(&mut self, out: &mut impl WriteColor) -> io::Result<()> {
+        *self = Self::new();
+
+        match out.supports_color() {
+            true => out.write_all(b"\x1b[0m"),
+            false => Ok(()),
+        }
+    }
+
+    /// Function that writes a cell, preceded by the colors that changed since the last cell, if any.
+    pub fn write_cell(&mut self, out: &mut impl WriteColor, cell: &Cell) -> io::Result<()> {
+        if !out.supports_color() {
+            return write!(out, "{}", cell.glyph);
+        }
+
+        let mut parameters = Vec::new();
+
+        if cell.glyph != BLANK && cell.foreground != self.foreground {
+            parameters.push(color_parameters(cell.foreground, true));
+            self.foreground = cell.foreground;
+        }
+        if cell.background != self.background {
+            parameters.push(color_parameters(cell.background, false));
+            self.background = cell.background;
+        }
+
+        if !parameters.is_empty() {
+            write!(out, "\x1b[{}m", parameters.join(";"))?;
+        }
+        write!(out, "{}", cell.glyph)
+    }
+}
+
+/// Function that returns a cell that looks the same but can be sent with fewer bytes: cells whose glyph is fully
+/// covered by one color become blank cells with that background.
+pub fn flatten(cell: Cell) -> Cell {
+    let background = match cell.glyph {
+        BLANK | '\u{2800}' => cell.background,
+        '█' => cell.foreground,
+        _ if cell.foreground == cell.background => cell.background,
+        _ => return cell,
+    };
+
+    Cell {
+        glyph: BLANK,
+        foreground: None,
+        background,
+    }
+}
+
+/// Function that rounds the channels of the 24-bit colors of a cell to multiples of `step`, so neighbouring cells
+/// share colors more often. A step of 0 or 1 leaves the colors as they are.
+pub fn snap(cell: Cell, step: u8) -> Cell {
+    let snap_color = |color: Option<Color>| match (color, step) {
+        (Some(Color::Rgb(red, green, blue)), 2..) => {
+            let snap_channel =
+                |c: u8| ((c as u16 + step as u16 / 2) / step as u16 * step as u16).min(255) as u8;
+            Some(Color::Rgb(
+                snap_channel(red),
+                snap_channel(green),
+                snap_channel(blue),
+            ))
+        }
+        _ => color,
+    };
+
+    Cell {
+        foreground: snap_color(cell.foreground),
+        background: snap_color(cell.background),
+        ..cell
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::color::ColorDepth;
+    use termcolor::Buffer;
+
+    /// Function that writes cells with a new writer and returns the output.
+    fn write_cells(mut buffer: Buffer, cells: &[Cell]) -> String {
+        let mut writer = AnsiWriter::new();
+        writer.reset(&mut buffer).unwrap();
+        cells
+            .iter()
+            .for_each(|cell| writer.write_cell(&mut buffer, cell).unwrap());
+
+        String::from_utf8(buffer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn sends_only_the_colors_that_change() {
+        let cell = |glyph, foreground, background| Cell {
+            glyph,
+            foreground,
+            background,
+        };
+        let cells = [
+            cell('a', Some(Color::Red), None),
+            cell('b', Some(Color::Red), None),
+            cell(' ', None, Some(Color::Blue)),
+            cell('c', Some(Color::Red), Some(Color::Blue)),
+        ];
+
+        assert_eq!(
+            write_cells(Buffer::ansi(), &cells),
+            "\x1b[0m\x1b[31mab\x1b[44m c"
+        );
+    }
+
+    #[test]
+    fn drops_colors_when_they_are_not_supported() {
+        let cells = [
+            Cell {
+                glyph: 'a',
+                foreground: Some(Color::Rgb(1, 2, 3)),
+                background: Some(Color::Green),
+            },
+            Cell {
+                glyph: ' ',
+                foreground: None,
+                background: Some(Color::Blue),
+            },
+        ];
+
+        assert_eq!(write_cells(Buffer::no_color(), &cells), "a ");
+    }
+
+    #[test]
+    fn sends_bright_colors_with_their_own_codes() {
+        assert_eq!(color_parameters(Some(Color::Ansi256(8)), true), "90");
+        assert_eq!(color_parameters(Some(Color::Ansi256(15)), true), "97");
+        assert_eq!(color_parameters(Some(Color::Ansi256(9)), false), "101");
+        assert_eq!(color_parameters(Some(Color::Ansi256(16)), true), "38;5;16");
+    }
+
+    #[test]
+    fn sends_16_colors_without_the_256_palette() {
+        (0..=255u8).step_by(15).for_each(|red| {
+            (0..=255u8).step_by(15).for_each(|green| {
+                [0, 128, 255].iter().for_each(|&blue| {
+                    let color = ColorDepth::Ansi16.color(red, green, blue);
+                    [true, false].iter().for_each(|&foreground| {
+                        let parameters = color_parameters(Some(color), foreground);
+                        assert!(!parameters.contains(';'), "{parameters}");
+                    });
+                });
+            });
+        });
+    }
+}
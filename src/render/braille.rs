@@ -0,0 +1,90 @@
+//! Module that draws frames with braille characters, a block of 2x4 pixels per cell.
+
+use crate::feed::frame::{Frame, Pixel};
+use crate::render::color::{BAYER_4X4, ColorDepth};
+use crate::render::grid::{Cell, CellGrid};
+
+/// Size, in pixels, of the block drawn in each cell.
+pub const CELL_SIZE: (usize, usize) = (2, 4);
+
+/// First character of the braille block, the one without any dot raised.
+const BRAILLE_BLANK: u32 = 0x2800;
+
+/// Bit raised in the braille character for each pixel of the block, indexed by `[y][x]`.
+const DOT_BITS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// Enum that represents how the brightness of a pixel decides whether its dot is raised.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DotFill {
+    /// Dots are raised for pixels brighter than the mean brightness of the frame. Best for text and line art.
+    Threshold,
+    /// Dots are raised following an ordered (Bayer) dither of the brightness. Best for gradients and video.
+    #[default]
+    Ordered,
+}
+
+impl DotFill {
+    /// Function that returns whether the dot of the pixel at (x, y) of the frame is raised.
+    pub fn is_raised(&self, grey_scale: u8, x: usize, y: usize, mean: u8) -> bool {
+        match self {
+            Self::Threshold => grey_scale > mean,
+            Self::Ordered => {
+                let threshold = BAYER_4X4[y % 4][x % 4] as u16 * 16 + 8;
+                grey_scale as u16 > threshold
+            }
+        }
+    }
+}
+
+/// Function that returns the braille character and the average color of the block whose top-left pixel is (x, y).
+pub fn cell(frame: &Frame, fill: DotFill, x: usize, y: usize, mean: u8) -> (char, [u8; 3]) {
+    let (mut bits, mut sum, mut count) = (0, [0u32; 3], 0);
+
+    (0..CELL_SIZE.1).for_each(|dy| {
+        (0..CELL_SIZE.0).for_each(|dx| {
+            if let Some(pixel) = frame.pixel(x + dx, y + dy) {
+                if fill.is_raised(pixel.grey_scale, x + dx, y + dy, mean) {
+                    bits |= DOT_BITS[dy][dx];
+                }
+                sum[0] += pixel.red as u32;
+                sum[1] += pixel.green as u32;
+                sum[2] += pixel.blue as u32;
+                count += 1;
+            }
+        })
+    });
+
+    let count = count.max(1);
+    let character = char::from_u32(BRAILLE_BLANK + bits).unwrap_or(' ');
+
+    (character, sum.map(|channel| (channel / count) as u8))
+}
+
+/// Function that returns the mean brightness of the frame.
+fn mean_grey_scale(pixels: &[Pixel]) -> u8 {
+    match pixels.len() {
+        0 => 0,
+        len => (pixels.iter().map(|p| p.grey_scale as u64).sum::<u64>() / len as u64) as u8,
+    }
+}
+
+/// Function that returns the cells that draw each 2x4 block of the frame as one braille character.
+pub fn render(frame: &Frame, fill: DotFill, depth: ColorDepth) -> CellGrid {
+    let (width, height) = (frame.frame_size.x as usize, frame.frame_size.y as usize);
+    let mean = mean_grey_scale(&frame.pixels);
+
+    CellGrid::from_fn(
+        width.div_ceil(CELL_SIZE.0),
+        height.div_ceil(CELL_SIZE.1),
+        |column, row| {
+            let (glyph, [red, green, blue]) =
+                cell(frame, fill, column * CELL_SIZE.0, row * CELL_SIZE.1, mean);
+
+            Cell {
+                glyph,
+                foreground: Some(depth.color(red, green, blue)),
+                background: None,
+            }
+        },
+    )
+}
@@ -0,0 +1,450 @@
+//! Module that maps frame colors to what the terminal can show (24-bit, the xterm 256 palette or the 16 ANSI colors).
+
+use crate::config::take_option;
+use crate::feed::frame::{Frame, Pixel, Size};
+use std::error::Error;
+use termcolor::Color;
+
+/// Bayer matrix used by ordered dithering, with values from 0 to 15.
+pub const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Levels of each channel in the 6x6x6 color cube of the xterm 256 palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Colors of the 16 ANSI colors, as xterm draws them by default.
+const ANSI_16: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [205, 0, 0],
+    [0, 205, 0],
+    [205, 205, 0],
+    [0, 0, 238],
+    [205, 0, 205],
+    [0, 205, 205],
+    [229, 229, 229],
+    [127, 127, 127],
+    [255, 0, 0],
+    [0, 255, 0],
+    [255, 255, 0],
+    [92, 92, 255],
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 255],
+];
+
+/// Prefixes of the `TERM` of terminals that show 24-bit colors even when they don't set `COLORTERM`.
+const TRUECOLOR_TERMS: [&str; 5] = [
+    "xterm-kitty",
+    "xterm-ghostty",
+    "foot",
+    "alacritty",
+    "wezterm",
+];
+
+/// Prefixes of the `TERM` of consoles and old terminals limited to the 16 ANSI colors.
+const ANSI_16_TERMS: [&str; 5] = ["linux", "vt", "dumb", "ansi", "cons25"];
+
+/// Enum that represents how many colors the terminal can show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ColorDepth {
+    /// 24-bit colors.
+    #[default]
+    TrueColor,
+    /// The xterm 256 palette (only the color cube and the grey ramp are used, their colors are the same everywhere).
+    Ansi256,
+    /// The 16 ANSI colors, the only ones in the Linux console and in older terminals.
+    Ansi16,
+}
+
+impl ColorDepth {
+    /// Function that picks the color depth from the values of the `COLORTERM` and `TERM` environment variables.
+    /// Terminals that aren't known to be limited are assumed to show 24-bit colors, like most current ones do.
+    pub fn detect(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        if let Some("truecolor" | "24bit") = colorterm {
+            return Self::TrueColor;
+        }
+
+        match term {
+            Some(term)
+                if term.ends_with("-direct")
+                    || term.contains("truecolor")
+                    || TRUECOLOR_TERMS.iter().any(|known| term.starts_with(known)) =>
+            {
+                Self::TrueColor
+            }
+            Some(term) if term.contains("256color") => Self::Ansi256,
+            Some(term)
+                if term.contains("16color")
+                    || ANSI_16_TERMS
+                        .iter()
+                        .any(|limited| term.starts_with(limited)) =>
+            {
+                Self::Ansi16
+            }
+            _ => Self::TrueColor,
+        }
+    }
+
+    /// Function that returns the closest color the terminal can show, both as the color to send and as its RGB value.
+    pub fn quantize(&self, rgb: [u8; 3]) -> (Color, [u8; 3]) {
+        match self {
+            Self::TrueColor => (Color::Rgb(rgb[0], rgb[1], rgb[2]), rgb),
+            Self::Ansi256 => {
+                let (index, palette_rgb) = nearest_256(rgb);
+                (Color::Ansi256(index), palette_rgb)
+            }
+            Self::Ansi16 => {
+                let index = nearest_index(&ANSI_16, rgb);
+                (ansi_16_color(index), ANSI_16[index])
+            }
+        }
+    }
+
+    /// Function that returns the color to send to the terminal for an RGB value.
+    pub fn color(&self, red: u8, green: u8, blue: u8) -> Color {
+        self.quantize([red, green, blue]).0
+    }
+
+    /// Function that returns the distance between two neighbouring colors of the palette, used to scale dithering.
+    fn step(&self) -> f32 {
+        match self {
+            Self::TrueColor => 0.,
+            Self::Ansi256 => 40.,
+            Self::Ansi16 => 128.,
+        }
+    }
+}
+
+/// Function that returns the color for one of the 16 ANSI colors. termcolor has no variant for the bright ones, so
+/// they are given as their index, which `ansi::color_parameters` sends with the bright SGR codes (90-97 and 100-107).
+fn ansi_16_color(index: usize) -> Color {
+    match index {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        7 => Color::White,
+        _ => Color::Ansi256(index as u8),
+    }
+}
+
+/// Function that returns the squared distance between two colors.
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    (0..3)
+        .map(|c| (a[c] as i32 - b[c] as i32).pow(2) as u32)
+        .sum()
+}
+
+/// Function that returns the index of the closest color of a palette.
+fn nearest_index(palette: &[[u8; 3]], rgb: [u8; 3]) -> usize {
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, color)| distance(**color, rgb))
+        .map_or(0, |(i, _)| i)
+}
+
+/// Function that returns the index and the color of the closest entry of the xterm 256 palette, looking at the
+/// closest color of the cube and the closest grey.
+pub fn nearest_256(rgb: [u8; 3]) -> (u8, [u8; 3]) {
+    let level = |value: u8| match value {
+        0..48 => 0,
+        48..115 => 1,
+        _ => (value - 35) / 40,
+    };
+    let [red, green, blue] = rgb.map(level);
+    let cube_index = 16 + 36 * red + 6 * green + blue;
+    let cube_rgb = [red, green, blue].map(|l| CUBE_LEVELS[l as usize]);
+
+    let average = (rgb.iter().map(|&c| c as u32).sum::<u32>() / 3) as u8;
+    let grey_level = match average {
+        0..8 => 0,
+        239.. => 23,
+        _ => (average - 3) / 10,
+    };
+    let grey_index = 232 + grey_level;
+    let grey_rgb = [8 + 10 * grey_level; 3];
+
+    match distance(grey_rgb, rgb) < distance(cube_rgb, rgb) {
+        true => (grey_index, grey_rgb),
+        false => (cube_index, cube_rgb),
+    }
+}
+
+/// Enum that represents how colors between the ones of the palette are approximated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Dither {
+    /// Each pixel gets the closest color.
+    #[default]
+    None,
+    /// Each pixel is nudged by a 4x4 Bayer matrix before taking the closest color. Stable between frames.
+    Bayer,
+    /// The error of each pixel is spread to its neighbours. Smoother, but noisier on video.
+    FloydSteinberg,
+}
+
+/// Struct that represents the colors used by the character renderers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct ColorOptions {
+    pub depth: ColorDepth,
+    pub dither: Dither,
+    /// Step 24-bit colors are rounded to (0 leaves them as they are). Coarser colors make longer runs of cells with
+    /// the same color, which need fewer escape sequences.
+    pub snap: u8,
+}
+
+impl ColorOptions {
+    /// Function that removes `--colors auto|truecolor|256|16`, `--dither none|bayer|floyd-steinberg` and
+    /// `--snap STEP` from the arguments and parses them. `auto`, the default, uses the color depth detected for the terminal.
+    pub fn take_from_args(
+        args: &mut Vec<String>,
+        detected: ColorDepth,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let depth = match take_option(args, "--colors")?.as_deref() {
+            None | Some("auto") => detected,
+            Some("truecolor") => ColorDepth::TrueColor,
+            Some("256") => ColorDepth::Ansi256,
+            Some("16") => ColorDepth::Ansi16,
+            Some(depth) => {
+                return Err(format!(
+                    "Invalid color depth `{depth}`, expected auto, truecolor, 256 or 16."
+                )
+                .into());
+            }
+        };
+
+        let dither = match take_option(args, "--dither")?.as_deref() {
+            None | Some("none") => Dither::None,
+            Some("bayer") => Dither::Bayer,
+            Some("floyd-steinberg") => Dither::FloydSteinberg,
+            Some(dither) => {
+                return Err(format!(
+                    "Invalid dither `{dither}`, expected none, bayer or floyd-steinberg."
+                )
+                .into());
+            }
+        };
+
+        let snap = match take_option(args, "--snap")? {
+            Some(step) => step.parse().map_err(|_| {
+                format!("Invalid snap step `{step}`, expected a number from 0 to 255.")
+            })?,
+            None => 0,
+        };
+
+        Ok(Self {
+            depth,
+            dither,
+            snap,
+        })
+    }
+
+    /// Function that returns a copy of the frame whose colors are already the ones of the palette, or `None` when
+    /// the frame can be drawn as it is.
+    pub fn dither_frame(&self, frame: &Frame) -> Option<Frame> {
+        if self.depth == ColorDepth::TrueColor || self.dither == Dither::None {
+            return None;
+        }
+
+        let width = frame.frame_size.x as usize;
+        let step = self.depth.step();
+        let mut errors = vec![[0f32; 3]; frame.pixels.len()];
+
+        let pixels = frame
+            .pixels
+            .iter()
+            .enumerate()
+            .map(|(i, pixel)| {
+                let (x, y) = (i % width.max(1), i / width.max(1));
+                let offset = match self.dither {
+                    Dither::Bayer => (BAYER_4X4[y % 4][x % 4] as f32 + 0.5) / 16. - 0.5,
+                    _ => 0.,
+                } * step;
+
+                let wanted = [pixel.red, pixel.green, pixel.blue]
+                    .map(|c| c as f32)
+                    .iter()
+                    .zip(errors[i])
+                    .map(|(c, error)| c + error + offset)
+                    .collect::<Vec<f32>>();
+                let (_, [red, green, blue]) = self
+                    .depth
+                    .quantize([0, 1, 2].map(|c| wanted[c].clamp(0., 255.) as u8));
+
+                if self.dither == Dither::FloydSteinberg {
+                    let error = [red, green, blue]
+                        .iter()
+                        .zip(&wanted)
+                        .map(|(&got, want)| want - got as f32)
+                        .collect::<Vec<f32>>();
+                    let neighbours = [
+                        (x + 1 < width, i + 1, 7. / 16.),
+                        (x > 0, i + width - 1, 3. / 16.),
+                        (true, i + width, 5. / 16.),
+                        (x + 1 < width, i + width + 1, 1. / 16.),
+                    ];
+                    neighbours.iter().for_each(|&(inside, j, weight)| {
+                        if let Some(neighbour) = errors.get_mut(j).filter(|_| inside) {
+                            (0..3).for_each(|c| neighbour[c] += error[c] * weight);
+                        }
+                    });
+                }
+
+                Pixel {
+                    red,
+                    green,
+                    blue,
+                    grey_scale: pixel.grey_scale,
+                }
+            })
+            .collect::<Vec<Pixel>>();
+
+        Some(Frame {
+            pixels,
+            frame_size: Size::new(frame.frame_size.x, frame.frame_size.y),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Function that creates a frame of the given size filled with one color.
+    fn flat_frame(width: u16, height: u16, rgb: [u8; 3]) -> Frame {
+        let pixel = || Pixel {
+            red: rgb[0],
+            green: rgb[1],
+            blue: rgb[2],
+            grey_scale: 0,
+        };
+
+        Frame {
+            pixels: (0..width as usize * height as usize)
+                .map(|_| pixel())
+                .collect(),
+            frame_size: Size::new(width, height),
+        }
+    }
+
+    /// Function that returns the average of each channel of a frame.
+    fn average(frame: &Frame) -> [f32; 3] {
+        let count = frame.pixels.len() as f32;
+        let sum = |channel: fn(&Pixel) -> u8| {
+            frame.pixels.iter().map(|p| channel(p) as f32).sum::<f32>() / count
+        };
+
+        [sum(|p| p.red), sum(|p| p.green), sum(|p| p.blue)]
+    }
+
+    #[test]
+    fn detects_the_color_depth() {
+        let detect = ColorDepth::detect;
+
+        assert_eq!(
+            detect(Some("truecolor"), Some("linux")),
+            ColorDepth::TrueColor
+        );
+        assert_eq!(detect(Some("24bit"), None), ColorDepth::TrueColor);
+        assert_eq!(detect(None, Some("xterm-direct")), ColorDepth::TrueColor);
+        [
+            "xterm-kitty",
+            "foot",
+            "foot-extra",
+            "alacritty",
+            "wezterm",
+            "xterm-ghostty",
+        ]
+        .iter()
+        .for_each(|term| assert_eq!(detect(None, Some(term)), ColorDepth::TrueColor, "{term}"));
+        assert_eq!(detect(None, Some("xterm-256color")), ColorDepth::Ansi256);
+        assert_eq!(detect(None, Some("screen-256color")), ColorDepth::Ansi256);
+        ["linux", "vt100", "vt220", "dumb", "ansi", "xterm-16color"]
+            .iter()
+            .for_each(|term| assert_eq!(detect(None, Some(term)), ColorDepth::Ansi16, "{term}"));
+        // Terminals that aren't known to be limited keep 24-bit colors.
+        assert_eq!(detect(None, Some("xterm")), ColorDepth::TrueColor);
+        assert_eq!(detect(None, None), ColorDepth::TrueColor);
+    }
+
+    #[test]
+    fn finds_the_nearest_256_color() {
+        assert_eq!(nearest_256([0, 0, 0]), (16, [0, 0, 0]));
+        assert_eq!(nearest_256([255, 255, 255]), (231, [255, 255, 255]));
+        assert_eq!(nearest_256([255, 0, 0]), (196, [255, 0, 0]));
+        assert_eq!(nearest_256([95, 135, 175]), (67, [95, 135, 175]));
+        // Greys between the levels of the cube come from the grey ramp.
+        assert_eq!(nearest_256([128, 128, 128]), (244, [128, 128, 128]));
+        assert_eq!(nearest_256([100, 102, 98]), (241, [98, 98, 98]));
+    }
+
+    #[test]
+    fn leaves_frames_alone_without_dithering() {
+        let frame = flat_frame(4, 4, [100, 100, 100]);
+        let options = |depth, dither| ColorOptions {
+            depth,
+            dither,
+            snap: 0,
+        };
+
+        assert!(
+            options(ColorDepth::Ansi16, Dither::None)
+                .dither_frame(&frame)
+                .is_none()
+        );
+        assert!(
+            options(ColorDepth::TrueColor, Dither::Bayer)
+                .dither_frame(&frame)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn dithers_with_the_bayer_matrix() {
+        let frame = flat_frame(8, 8, [100, 100, 100]);
+        let options = ColorOptions {
+            depth: ColorDepth::Ansi16,
+            dither: Dither::Bayer,
+            snap: 0,
+        };
+        let colors = |frame: &Frame| {
+            frame
+                .pixels
+                .iter()
+                .map(|p| [p.red, p.green, p.blue])
+                .collect::<Vec<[u8; 3]>>()
+        };
+        let dithered = colors(&options.dither_frame(&frame).unwrap());
+
+        assert!(dithered.iter().all(|color| ANSI_16.contains(color)));
+        assert!(dithered.iter().any(|color| *color != dithered[0]));
+        // The pattern repeats every 4 pixels and is the same for every frame, so it doesn't flicker.
+        assert_eq!(dithered[..4], dithered[4..8]);
+        assert_eq!(dithered[..8], dithered[32..40]);
+        assert_eq!(colors(&options.dither_frame(&frame).unwrap()), dithered);
+    }
+
+    #[test]
+    fn spreads_the_error_with_floyd_steinberg() {
+        let frame = flat_frame(16, 16, [100, 100, 100]);
+        let options = ColorOptions {
+            depth: ColorDepth::Ansi16,
+            dither: Dither::FloydSteinberg,
+            snap: 0,
+        };
+        let dithered = options.dither_frame(&frame).unwrap();
+
+        assert!(
+            dithered
+                .pixels
+                .iter()
+                .all(|p| ANSI_16.contains(&[p.red, p.green, p.blue]))
+        );
+        // Without dithering every pixel would be the closest grey (127), the mix keeps the average close to 100.
+        average(&dithered)
+            .iter()
+            .for_each(|channel| assert!((channel - 100.).abs() < 10., "{channel}"));
+    }
+}
@@ -0,0 +1,301 @@
+//! Module that implements the grid of cells drawn by the character renderers and the painter that only redraws the
+//! cells that changed since the last frame.
+
+use crate::render::ansi::{AnsiWriter, flatten, snap};
+use std::error::Error;
+use std::io::Write;
+use std::time::{Duration, Instant};
+use termcolor::{Buffer, Color};
+use termion::{clear, cursor};
+
+/// Time after which the whole grid is drawn again, fixing anything that went out of sync (e.g. text written by
+/// another program).
+pub const REPAINT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Struct that represents a cell of the terminal: a character and its colors (`None` is the default color).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub glyph: char,
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            glyph: ' ',
+            foreground: None,
+            background: None,
+        }
+    }
+}
+
+/// Struct that represents the cells drawn for a frame, row by row.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CellGrid {
+    pub columns: usize,
+    pub rows: usize,
+    pub cells: Vec<Cell>,
+}
+
+impl CellGrid {
+    /// Function that creates a grid from a function that returns the cell at each (column, row).
+    pub fn from_fn(columns: usize, rows: usize, cell: impl FnMut(usize, usize) -> Cell) -> Self {
+        let mut cell = cell;
+
+        Self {
+            columns,
+            rows,
+            cells: (0..rows)
+                .flat_map(|row| (0..columns).map(move |column| (column, row)))
+                .map(|(column, row)| cell(column, row))
+                .collect(),
+        }
+    }
+
+    /// Function that makes the cells cheaper to send without changing how they look (much): colors are snapped to
+    /// multiples of `snap_step` and cells covered by one color become blank.
+    pub fn simplify(&mut self, snap_step: u8) {
+        self.cells
+            .iter_mut()
+            .for_each(|cell| *cell = flatten(snap(*cell, snap_step)));
+    }
+
+    /// Function that returns the cell at (column, row).
+    pub fn cell(&self, column: usize, row: usize) -> Option<&Cell> {
+        match column < self.columns {
+            true => self.cells.get(row * self.columns + column),
+            false => None,
+        }
+    }
+}
+
+/// Struct that represents a run of cells of a row that have to be drawn, starting at `column`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub row: usize,
+    pub column: usize,
+    pub length: usize,
+}
+
+/// Function that returns the runs of cells that differ between two grids of the same size, or every row when
+/// there is no previous grid.
+pub fn changed_spans(previous: Option<&CellGrid>, grid: &CellGrid) -> Vec<Span> {
+    let mut spans = Vec::new();
+
+    (0..grid.rows).for_each(|row| {
+        let mut start = None;
+
+        (0..=grid.columns).for_each(|column| {
+            let changed = column < grid.columns
+                && match previous {
+                    Some(previous) => previous.cell(column, row) != grid.cell(column, row),
+                    None => true,
+                };
+
+            match (changed, start) {
+                (true, None) => start = Some(column),
+                (false, Some(first)) => {
+                    spans.push(Span {
+                        row,
+                        column: first,
+                        length: column - first,
+                    });
+                    start = None;
+                }
+                _ => {}
+            }
+        });
+    });
+
+    spans
+}
+
+/// Struct that represents what was last drawn in the terminal, used to only send the cells that changed.
+pub struct GridPainter {
+    previous: Option<CellGrid>,
+    last_repaint: Instant,
+}
+
+impl Default for GridPainter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GridPainter {
+    /// Function that creates a painter that draws the whole grid the first time.
+    pub fn new() -> Self {
+        Self {
+            previous: None,
+            last_repaint: Instant::now(),
+        }
+    }
+
+    /// Function that makes the next frame be drawn whole.
+    pub fn invalidate(&mut self) {
+        self.previous = None;
+    }
+
+    /// Function that loads a buffer with the cells of the grid that changed since the last one. The whole grid is
+    /// drawn when it wasn't drawn whole for `REPAINT_INTERVAL`, over what is on screen, and the screen is only
+    /// cleared when the size of the grid changed, so nothing flashes on terminals without synchronized output.
+    pub fn paint(
+        &mut self,
+        grid: CellGrid,
+        buffer: &mut Buffer,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let resized = self
+            .previous
+            .as_ref()
+            .is_none_or(|previous| (previous.columns, previous.rows) != (grid.columns, grid.rows));
+
+        let mut writer = AnsiWriter::new();
+        writer.reset(buffer)?;
+
+        if resized {
+            write!(buffer, "{}", clear::All)?;
+        }
+        if resized || self.last_repaint.elapsed() >= REPAINT_INTERVAL {
+            self.previous = None;
+            self.last_repaint = Instant::now();
+        }
+
+        changed_spans(self.previous.as_ref(), &grid)
+            .iter()
+            .try_for_each(|span| -> Result<(), Box<dyn Error + Send + Sync>> {
+                write!(
+                    buffer,
+                    "{}",
+                    cursor::Goto(span.column as u16 + 1, span.row as u16 + 1)
+                )?;
+
+                let start = span.row * grid.columns + span.column;
+                grid.cells[start..start + span.length]
+                    .iter()
+                    .try_for_each(|cell| writer.write_cell(buffer, cell))?;
+
+                Ok(())
+            })?;
+
+        writer.reset(buffer)?;
+        self.previous = Some(grid);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(columns: usize, rows: usize, glyph: char) -> CellGrid {
+        CellGrid::from_fn(columns, rows, |_, _| Cell {
+            glyph,
+            foreground: Some(Color::Rgb(255, 255, 255)),
+            background: None,
+        })
+    }
+
+    fn painted(painter: &mut GridPainter, grid: CellGrid) -> String {
+        let mut buffer = Buffer::ansi();
+        painter.paint(grid, &mut buffer).unwrap();
+        String::from_utf8(buffer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn finds_no_spans_in_an_unchanged_grid() {
+        let grid = grid(4, 3, '#');
+        assert_eq!(changed_spans(Some(&grid), &grid), []);
+    }
+
+    #[test]
+    fn finds_every_row_without_a_previous_grid() {
+        assert_eq!(
+            changed_spans(None, &grid(4, 2, '#')),
+            [
+                Span {
+                    row: 0,
+                    column: 0,
+                    length: 4
+                },
+                Span {
+                    row: 1,
+                    column: 0,
+                    length: 4
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_runs_of_changed_cells() {
+        let previous = grid(5, 2, '#');
+        let mut next = previous.clone();
+        next.cells[1].glyph = '@';
+        next.cells[2].glyph = '@';
+        next.cells[9].glyph = '@';
+
+        assert_eq!(
+            changed_spans(Some(&previous), &next),
+            [
+                Span {
+                    row: 0,
+                    column: 1,
+                    length: 2
+                },
+                Span {
+                    row: 1,
+                    column: 4,
+                    length: 1
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn paints_only_the_changed_cell() {
+        let mut painter = GridPainter::new();
+        painted(&mut painter, grid(3, 2, '#'));
+
+        let mut next = grid(3, 2, '#');
+        next.cells[4].glyph = '@';
+
+        assert_eq!(
+            painted(&mut painter, next),
+            format!(
+                "\x1b[0m{}\x1b[38;2;255;255;255m@\x1b[0m",
+                cursor::Goto(2, 2)
+            )
+        );
+    }
+
+    #[test]
+    fn paints_nothing_for_an_unchanged_grid() {
+        let mut painter = GridPainter::new();
+        painted(&mut painter, grid(3, 2, '#'));
+
+        assert_eq!(painted(&mut painter, grid(3, 2, '#')), "\x1b[0m\x1b[0m");
+    }
+
+    #[test]
+    fn clears_and_repaints_everything_on_resize() {
+        let mut painter = GridPainter::new();
+        painted(&mut painter, grid(3, 2, '#'));
+
+        let output = painted(&mut painter, grid(2, 2, '#'));
+        assert!(output.starts_with(&format!("\x1b[0m{}", clear::All)));
+        assert_eq!(output.matches('#').count(), 4);
+    }
+
+    #[test]
+    fn repaints_without_clearing_after_the_interval() {
+        let mut painter = GridPainter::new();
+        painted(&mut painter, grid(3, 2, '#'));
+        painter.last_repaint -= REPAINT_INTERVAL;
+
+        let output = painted(&mut painter, grid(3, 2, '#'));
+        assert!(!output.contains(&clear::All.to_string()));
+        assert_eq!(output.matches('#').count(), 6);
+    }
+}
@@ -0,0 +1,25 @@
+//! Module that draws frames with half-block characters, two vertical pixels per cell.
+
+use crate::feed::frame::Frame;
+use crate::render::color::ColorDepth;
+use crate::render::grid::{Cell, CellGrid};
+
+/// Character that paints the upper half of a cell with the foreground color and the lower half with the background.
+const UPPER_HALF_BLOCK: char = '▀';
+
+/// Function that returns the cells that draw each pair of rows of the frame as one row of half-block cells.
+pub fn render(frame: &Frame, depth: ColorDepth) -> CellGrid {
+    let (width, height) = (frame.frame_size.x as usize, frame.frame_size.y as usize);
+
+    CellGrid::from_fn(width, height.div_ceil(2), |x, row| {
+        let top = frame.pixel(x, row * 2);
+        // An odd number of rows leaves the last cell row without a bottom pixel.
+        let bottom = frame.pixel(x, row * 2 + 1);
+
+        Cell {
+            glyph: UPPER_HALF_BLOCK,
+            foreground: top.map(|p| depth.color(p.red, p.green, p.blue)),
+            background: bottom.map(|p| depth.color(p.red, p.green, p.blue)),
+        }
+    })
+}
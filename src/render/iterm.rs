@@ -0,0 +1,121 @@
+//! Module that encodes frames with the iTerm2 inline image protocol, also supported by WezTerm.
+
+use crate::render::base64;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::{ExtendedColorType, ImageEncoder, RgbImage};
+use std::error::Error;
+
+/// Quality used for JPEG frames when none is requested.
+pub const DEFAULT_JPEG_QUALITY: u8 = 80;
+
+/// Enum that represents the file format each frame is sent as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ItermFormat {
+    #[default]
+    Png,
+    /// JPEG with the given quality (1-100). Much smaller than PNG for camera and video frames.
+    Jpeg(u8),
+}
+
+/// Function that encodes an image as the `OSC 1337;File=` sequence that draws it at the cursor, one terminal pixel
+/// per image pixel. The cursor is left where it was.
+pub fn encode(
+    image: &RgbImage,
+    format: ItermFormat,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let (width, height) = image.dimensions();
+
+    let mut file = Vec::new();
+    match format {
+        ItermFormat::Png => PngEncoder::new(&mut file).write_image(
+            image.as_raw(),
+            width,
+            height,
+            ExtendedColorType::Rgb8,
+        )?,
+        ItermFormat::Jpeg(quality) => JpegEncoder::new_with_quality(
+            &mut file,
+            quality.clamp(1, 100),
+        )
+        .write_image(image.as_raw(), width, height, ExtendedColorType::Rgb8)?,
+    }
+
+    Ok(format!(
+        "\x1b]1337;File=inline=1;size={};width={width}px;height={height}px;preserveAspectRatio=0;doNotMoveCursor=1:{}\x07",
+        file.len(),
+        base64(&file)
+    )
+    .into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    /// Function that splits the sequence into its arguments and its decoded file.
+    fn parse(sequence: &[u8]) -> (String, Vec<u8>) {
+        let sequence = std::str::from_utf8(sequence).unwrap();
+        let body = sequence
+            .strip_prefix("\x1b]1337;File=")
+            .and_then(|body| body.strip_suffix('\x07'))
+            .unwrap();
+        let (arguments, payload) = body.split_once(':').unwrap();
+
+        (arguments.to_string(), decode_base64(payload))
+    }
+
+    fn decode_base64(text: &str) -> Vec<u8> {
+        let value = |c: u8| match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            _ => 63,
+        };
+
+        text.as_bytes()
+            .chunks(4)
+            .flat_map(|chunk| {
+                let data = chunk.iter().filter(|&&c| c != b'=').collect::<Vec<_>>();
+                let group = data.iter().enumerate().fold(0u32, |group, (i, &&c)| {
+                    group | (value(c) as u32) << (18 - 6 * i)
+                });
+                (0..data.len() - 1).map(move |i| (group >> (16 - 8 * i)) as u8)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn encodes_png_frames() {
+        let image = RgbImage::from_pixel(1, 1, Rgb([255, 0, 0]));
+        let (arguments, file) = parse(&encode(&image, ItermFormat::Png).unwrap());
+
+        assert_eq!(
+            arguments,
+            format!(
+                "inline=1;size={};width=1px;height=1px;preserveAspectRatio=0;doNotMoveCursor=1",
+                file.len()
+            )
+        );
+        assert!(file.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert_eq!(image::load_from_memory(&file).unwrap().to_rgb8(), image);
+    }
+
+    #[test]
+    fn encodes_jpeg_frames() {
+        let image = RgbImage::from_pixel(2, 3, Rgb([128, 128, 128]));
+        let (arguments, file) = parse(&encode(&image, ItermFormat::Jpeg(90)).unwrap());
+
+        assert_eq!(
+            arguments,
+            format!(
+                "inline=1;size={};width=2px;height=3px;preserveAspectRatio=0;doNotMoveCursor=1",
+                file.len()
+            )
+        );
+        assert!(file.starts_with(&[0xFF, 0xD8]));
+        assert!(file.ends_with(&[0xFF, 0xD9]));
+    }
+}
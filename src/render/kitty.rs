@@ -0,0 +1,152 @@
+//! Module that encodes frames with the kitty graphics protocol, for terminals like kitty and Ghostty.
+
+use crate::render::base64;
+use image::codecs::png::PngEncoder;
+use image::{ExtendedColorType, ImageEncoder, RgbImage};
+use std::error::Error;
+use std::io::Write;
+
+/// Id of the image (and of its placement) every frame is sent as, so the terminal replaces the previous frame
+/// instead of adding a new image.
+pub const IMAGE_ID: u32 = 1;
+
+/// Maximum size, in bytes, of the base64 payload of each escape sequence.
+pub const CHUNK_SIZE: usize = 4096;
+
+/// Enum that represents how the pixels of the frame are sent to the terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum KittyPayload {
+    /// Raw 24-bit RGB pixels. Cheap to encode but large.
+    #[default]
+    Rgb,
+    /// PNG compressed pixels. Smaller but slower to encode.
+    Png,
+}
+
+/// Function that encodes an image as the escape sequences that transmit and display it at the cursor, replacing
+/// the image sent before with the same id. When `cells` is given, the terminal scales the image to cover that many
+/// columns and rows instead of showing it at its size. The cursor is left where it was.
+pub fn encode(
+    image: &RgbImage,
+    payload: KittyPayload,
+    cells: Option<(u16, u16)>,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let (width, height) = image.dimensions();
+    let placement = match cells {
+        Some((columns, rows)) => format!(",c={columns},r={rows}"),
+        None => String::new(),
+    };
+
+    let (format, data) = match payload {
+        KittyPayload::Rgb => (format!("f=24,s={width},v={height}"), base64(image.as_raw())),
+        KittyPayload::Png => {
+            let mut png = Vec::new();
+            PngEncoder::new(&mut png).write_image(
+                image.as_raw(),
+                width,
+                height,
+                ExtendedColorType::Rgb8,
+            )?;
+            ("f=100".to_string(), base64(&png))
+        }
+    };
+
+    let chunks = data.as_bytes().chunks(CHUNK_SIZE).collect::<Vec<&[u8]>>();
+    let mut out = Vec::with_capacity(data.len() + chunks.len() * 32);
+
+    chunks.iter().enumerate().try_for_each(
+        |(i, chunk)| -> Result<(), Box<dyn Error + Send + Sync>> {
+            let more = (i + 1 < chunks.len()) as u8;
+
+            // Only the first chunk carries the keys, `q=2` keeps the terminal from answering.
+            match i {
+                0 => write!(
+                    out,
+                    "\x1b_Ga=T,{format},i={IMAGE_ID},p={IMAGE_ID}{placement},q=2,C=1,m={more};"
+                )?,
+                _ => write!(out, "\x1b_Gm={more};")?,
+            }
+            out.extend_from_slice(chunk);
+            out.extend_from_slice(b"\x1b\\");
+
+            Ok(())
+        },
+    )?;
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Function that splits the escape sequences into their keys and their payload.
+    fn sequences(encoded: &[u8]) -> Vec<(String, String)> {
+        let encoded = String::from_utf8(encoded.to_vec()).unwrap();
+        let sequences = encoded.strip_suffix("\x1b\\").unwrap().split("\x1b\\");
+
+        sequences
+            .map(|sequence| {
+                let (keys, payload) = sequence
+                    .strip_prefix("\x1b_G")
+                    .unwrap()
+                    .split_once(';')
+                    .unwrap();
+                (keys.to_string(), payload.to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn splits_large_images_in_chunks() {
+        // 40x40 RGB pixels are 4800 bytes, 6400 once in base64, so they take a full chunk and part of another.
+        let image = RgbImage::from_fn(40, 40, |x, y| image::Rgb([x as u8, y as u8, (x * y) as u8]));
+        let encoded = encode(&image, KittyPayload::Rgb, Some((10, 5))).unwrap();
+        let sequences = sequences(&encoded);
+
+        assert_eq!(sequences.len(), 2);
+        assert_eq!(
+            sequences[0].0,
+            format!("a=T,f=24,s=40,v=40,i={IMAGE_ID},p={IMAGE_ID},c=10,r=5,q=2,C=1,m=1")
+        );
+        assert_eq!(sequences[1].0, "m=0");
+        assert_eq!(sequences[0].1.len(), CHUNK_SIZE);
+        assert_eq!(sequences[1].1.len(), 6400 - CHUNK_SIZE);
+        assert_eq!(
+            sequences
+                .iter()
+                .map(|(_, payload)| payload.as_str())
+                .collect::<String>(),
+            base64(image.as_raw())
+        );
+    }
+
+    #[test]
+    fn marks_every_chunk_but_the_last() {
+        // 64x64 RGB pixels are 12288 bytes, exactly four chunks once in base64.
+        let image = RgbImage::new(64, 64);
+        let sequences = sequences(&encode(&image, KittyPayload::Rgb, None).unwrap());
+
+        assert_eq!(sequences.len(), 4);
+        assert!(sequences[0].0.starts_with("a=T,f=24,s=64,v=64,"));
+        assert!(!sequences[0].0.contains("c="));
+        assert!(sequences[0].0.ends_with("m=1"));
+        assert_eq!(sequences[1].0, "m=1");
+        assert_eq!(sequences[2].0, "m=1");
+        assert_eq!(sequences[3].0, "m=0");
+        assert!(
+            sequences
+                .iter()
+                .all(|(_, payload)| payload.len() == CHUNK_SIZE)
+        );
+    }
+
+    #[test]
+    fn sends_small_images_in_one_sequence() {
+        let sequences = sequences(&encode(&RgbImage::new(2, 2), KittyPayload::Png, None).unwrap());
+
+        assert_eq!(sequences.len(), 1);
+        assert!(sequences[0].0.starts_with("a=T,f=100,"));
+        assert!(sequences[0].0.ends_with("m=0"));
+    }
+}
@@ -0,0 +1,142 @@
+//! Module that draws frames with block mosaic characters (quadrants and sextants), two colors per cell.
+
+use crate::feed::frame::Frame;
+use crate::render::color::ColorDepth;
+use crate::render::grid::{Cell, CellGrid};
+
+/// Quadrant characters indexed by their pattern, where bit 0 is the top-left quarter, bit 1 the top-right,
+/// bit 2 the bottom-left and bit 3 the bottom-right.
+const QUADRANTS: [char; 16] = [
+    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+];
+
+/// First character of the sextant block (U+1FB00), the one with only the top-left sixth filled.
+const SEXTANT_FIRST: u32 = 0x1FB00;
+
+/// Enum that represents the set of block characters used to draw a cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mosaic {
+    /// 2x2 pixels per cell.
+    Quadrant,
+    /// 2x3 pixels per cell.
+    Sextant,
+}
+
+impl Mosaic {
+    /// Function that returns the size, in pixels, of the block drawn in each cell.
+    pub fn cell_size(&self) -> (usize, usize) {
+        match self {
+            Self::Quadrant => (2, 2),
+            Self::Sextant => (2, 3),
+        }
+    }
+
+    /// Function that returns the character that fills the pixels of the pattern with the foreground color. Bits
+    /// follow the pixels of the block from left to right and top to bottom.
+    pub fn glyph(&self, pattern: u32) -> char {
+        match self {
+            Self::Quadrant => QUADRANTS[pattern as usize & 0xF],
+            // The sextant block skips the patterns that already exist as other characters.
+            Self::Sextant => match pattern & 0x3F {
+                0 => ' ',
+                0b010101 => '▌',
+                0b101010 => '▐',
+                0b111111 => '█',
+                pattern => {
+                    let skipped = (pattern > 0b010101) as u32 + (pattern > 0b101010) as u32;
+                    char::from_u32(SEXTANT_FIRST + pattern - 1 - skipped).unwrap_or(' ')
+                }
+            },
+        }
+    }
+}
+
+/// Struct that represents a cell drawn with a mosaic character.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MosaicCell {
+    pub glyph: char,
+    pub foreground: [u8; 3],
+    pub background: [u8; 3],
+}
+
+/// Function that returns the cell that best draws the block whose top-left pixel is (x, y). Every pattern is
+/// tried and the one whose two average colors are closest to the pixels (least squared error) wins.
+pub fn cell(frame: &Frame, mosaic: Mosaic, x: usize, y: usize) -> MosaicCell {
+    let (cell_width, cell_height) = mosaic.cell_size();
+    let count = cell_width * cell_height;
+
+    let colors = (0..count)
+        .map(|i| {
+            frame
+                .pixel(x + i % cell_width, y + i / cell_width)
+                .map(|p| [p.red as u32, p.green as u32, p.blue as u32])
+        })
+        .collect::<Vec<Option<[u32; 3]>>>();
+
+    let average = |sum: [u32; 3], n: u32| sum.map(|channel| (channel / n.max(1)) as u8);
+
+    // Minimizing the squared error is the same as maximizing |sum_fg|²/n_fg + |sum_bg|²/n_bg.
+    let score = |sum: [u32; 3], n: u32| match n {
+        0 => 0.,
+        n => sum.iter().map(|&c| (c as f64) * (c as f64)).sum::<f64>() / n as f64,
+    };
+
+    let (_, pattern, foreground, background) = (0..1u32 << count)
+        .map(|pattern| {
+            let (mut fg, mut bg, mut n_fg, mut n_bg) = ([0u32; 3], [0u32; 3], 0, 0);
+
+            colors.iter().enumerate().for_each(|(i, color)| {
+                if let Some(color) = color {
+                    let (sum, n) = match pattern & (1 << i) != 0 {
+                        true => (&mut fg, &mut n_fg),
+                        false => (&mut bg, &mut n_bg),
+                    };
+                    (0..3).for_each(|c| sum[c] += color[c]);
+                    *n += 1;
+                }
+            });
+
+            (
+                score(fg, n_fg) + score(bg, n_bg),
+                pattern,
+                average(fg, n_fg),
+                average(bg, n_bg),
+            )
+        })
+        .fold((f64::MIN, 0, [0; 3], [0; 3]), |best, candidate| {
+            match candidate.0 > best.0 {
+                true => candidate,
+                false => best,
+            }
+        });
+
+    MosaicCell {
+        glyph: mosaic.glyph(pattern),
+        foreground,
+        background,
+    }
+}
+
+/// Function that returns the cells that draw each block of the frame as one mosaic character.
+pub fn render(frame: &Frame, mosaic: Mosaic, depth: ColorDepth) -> CellGrid {
+    let (width, height) = (frame.frame_size.x as usize, frame.frame_size.y as usize);
+    let (cell_width, cell_height) = mosaic.cell_size();
+
+    CellGrid::from_fn(
+        width.div_ceil(cell_width),
+        height.div_ceil(cell_height),
+        |column, row| {
+            let MosaicCell {
+                glyph,
+                foreground: [fg_red, fg_green, fg_blue],
+                background: [bg_red, bg_green, bg_blue],
+            } = cell(frame, mosaic, column * cell_width, row * cell_height);
+
+            Cell {
+                glyph,
+                foreground: Some(depth.color(fg_red, fg_green, fg_blue)),
+                background: Some(depth.color(bg_red, bg_green, bg_blue)),
+            }
+        },
+    )
+}
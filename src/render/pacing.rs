@@ -0,0 +1,98 @@
+//! Module that paces the frames drawn to how fast the terminal takes them, so frames don't queue up in the pty and
+//! the one shown is always the freshest.
+
+use std::time::{Duration, Instant};
+
+/// How much longer than a print the terminal is given before the next frame, leaving it time to draw the last one.
+pub const HEADROOM: f64 = 1.5;
+
+/// Weight of the last print in the average print time. Higher values react faster to the terminal slowing down.
+const SMOOTHING: f64 = 0.25;
+
+/// Struct that represents how long printing a frame takes on average and when the next frame can be drawn.
+#[derive(Clone, Copy, Debug)]
+pub struct FramePacer {
+    average_print: Duration,
+    next_frame: Instant,
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FramePacer {
+    /// Function that creates a pacer that draws the first frame right away.
+    pub fn new() -> Self {
+        Self {
+            average_print: Duration::ZERO,
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// Function that returns whether a frame can be drawn at `now`, frames that come earlier should be dropped.
+    pub fn is_ready(&self, now: Instant) -> bool {
+        now >= self.next_frame
+    }
+
+    /// Function that returns how long after `now` the next frame can be drawn, zero if it can be drawn already.
+    pub fn time_until_ready(&self, now: Instant) -> Duration {
+        self.next_frame.saturating_duration_since(now)
+    }
+
+    /// Function that records a print that started at `start` and ended at `end`, and delays the next frame until
+    /// the terminal should be done with it.
+    pub fn record_print(&mut self, start: Instant, end: Instant) {
+        let print = end.saturating_duration_since(start);
+
+        self.average_print = match self.average_print.is_zero() {
+            true => print,
+            false => self.average_print.mul_f64(1. - SMOOTHING) + print.mul_f64(SMOOTHING),
+        };
+        self.next_frame = start + self.average_print.mul_f64(HEADROOM);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_the_first_frame_right_away() {
+        let pacer = FramePacer::new();
+        let now = Instant::now();
+
+        assert!(pacer.is_ready(now));
+        assert_eq!(pacer.time_until_ready(now), Duration::ZERO);
+    }
+
+    #[test]
+    fn waits_for_the_print_with_headroom() {
+        let mut pacer = FramePacer::new();
+        let start = Instant::now();
+        pacer.record_print(start, start + Duration::from_millis(10));
+
+        let ready = start + Duration::from_millis(10).mul_f64(HEADROOM);
+        assert!(!pacer.is_ready(start + Duration::from_millis(10)));
+        assert!(!pacer.is_ready(ready - Duration::from_micros(1)));
+        assert!(pacer.is_ready(ready));
+        assert_eq!(pacer.time_until_ready(start), ready - start);
+        assert_eq!(
+            pacer.time_until_ready(ready + Duration::from_millis(1)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn smooths_the_print_time() {
+        let mut pacer = FramePacer::new();
+        let start = Instant::now();
+        pacer.record_print(start, start + Duration::from_millis(10));
+        pacer.record_print(start, start + Duration::from_millis(50));
+
+        // The average moves a quarter of the way from 10ms to 50ms.
+        let average = Duration::from_millis(20);
+        assert_eq!(pacer.time_until_ready(start), average.mul_f64(HEADROOM));
+    }
+}
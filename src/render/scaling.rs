@@ -0,0 +1,84 @@
+//! Module that scales frames to the terminal keeping their aspect ratio.
+
+use crate::FILTER;
+use image::{RgbImage, imageops};
+use std::error::Error;
+use std::str::FromStr;
+
+/// Enum that represents how a frame is scaled to the terminal when their aspect ratios differ.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Scaling {
+    /// The whole frame is shown, with black bars on the sides or above and below it.
+    #[default]
+    Fit,
+    /// The whole terminal is covered, cutting the edges of the frame that don't fit.
+    Fill,
+    /// The frame is stretched to the terminal, distorting it.
+    Stretch,
+}
+
+impl FromStr for Scaling {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fit" => Ok(Self::Fit),
+            "fill" => Ok(Self::Fill),
+            "stretch" => Ok(Self::Stretch),
+            _ => Err(format!("Invalid scaling `{s}`, expected fit, fill or stretch.").into()),
+        }
+    }
+}
+
+impl Scaling {
+    /// Function that returns the size the image is resized to before it is placed on the canvas. `pixel_aspect` is
+    /// the width of a pixel of the canvas divided by its height, as they are drawn in the terminal.
+    pub fn scaled_size(
+        &self,
+        image: (u32, u32),
+        canvas: (u32, u32),
+        pixel_aspect: f64,
+    ) -> (u32, u32) {
+        let (width, height) = (image.0.max(1) as f64, image.1.max(1) as f64);
+        let (canvas_width, canvas_height) = (canvas.0 as f64, canvas.1 as f64);
+
+        // Sizes the image takes in canvas pixels when it matches the width or the height of the canvas.
+        let matching_width = (
+            canvas.0,
+            (canvas_width * pixel_aspect * height / width).round(),
+        );
+        let matching_height = (
+            (canvas_height * width / (height * pixel_aspect)).round(),
+            canvas.1,
+        );
+        let wider = width / height > canvas_width * pixel_aspect / canvas_height.max(1.);
+
+        let (x, y) = match (self, wider) {
+            (Self::Stretch, _) => return canvas,
+            (Self::Fit, true) | (Self::Fill, false) => (matching_width.0, matching_width.1 as u32),
+            (Self::Fit, false) | (Self::Fill, true) => {
+                (matching_height.0 as u32, matching_height.1)
+            }
+        };
+
+        (x.max(1), y.max(1))
+    }
+
+    /// Function that scales the image to a canvas of the given size, centered on a black background or cropped
+    /// around its center depending on the scaling.
+    pub fn scale(&self, image: &RgbImage, canvas: (u32, u32), pixel_aspect: f64) -> RgbImage {
+        let (width, height) = self.scaled_size(image.dimensions(), canvas, pixel_aspect);
+        let resized = imageops::resize(image, width, height, FILTER);
+
+        if (width, height) == canvas {
+            return resized;
+        }
+
+        let mut output = RgbImage::new(canvas.0, canvas.1);
+        let x = (canvas.0 as i64 - width as i64) / 2;
+        let y = (canvas.1 as i64 - height as i64) / 2;
+        imageops::replace(&mut output, &resized, x, y);
+
+        output
+    }
+}
@@ -0,0 +1,359 @@
+//! Module that encodes frames as sixel images, for terminals that can show real pixels (xterm, foot, WezTerm...).
+
+use image::RgbImage;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Number of colors of the palette when none is requested (the most that most terminals support).
+pub const DEFAULT_COLORS: usize = 256;
+
+/// Number of pixel rows encoded by each sixel character.
+const BAND_HEIGHT: u32 = 6;
+
+/// Bits kept per channel when grouping similar colors before quantizing.
+const HISTOGRAM_BITS: u32 = 5;
+
+/// Struct that represents the pixels that fall in one entry of the histogram.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+struct Bucket {
+    count: u64,
+    sum: [u64; 3],
+}
+
+/// Struct that represents the palette an image is quantized to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    /// Function that builds a palette of at most `max_colors` colors with the median cut algorithm: the box of colors
+    /// with the widest channel is split at its median until there are enough boxes, and each box becomes the average
+    /// of its colors. The result only depends on the pixels, so the same image always gives the same palette.
+    pub fn median_cut(image: &RgbImage, max_colors: usize) -> Self {
+        let mut histogram: HashMap<[u8; 3], Bucket> = HashMap::new();
+        image.pixels().for_each(|pixel| {
+            let bucket = histogram.entry(reduce(pixel.0)).or_default();
+            bucket.count += 1;
+            (0..3).for_each(|c| bucket.sum[c] += pixel.0[c] as u64);
+        });
+
+        let mut entries = histogram
+            .into_iter()
+            .map(|(key, bucket)| (expand(key), bucket))
+            .collect::<Vec<([u8; 3], Bucket)>>();
+        entries.sort_unstable();
+
+        let mut boxes = match entries.is_empty() {
+            true => Vec::new(),
+            false => vec![entries],
+        };
+
+        while boxes.len() < max_colors.max(1) {
+            let widest = boxes
+                .iter()
+                .enumerate()
+                .filter(|(_, colors)| colors.len() > 1)
+                .map(|(i, colors)| {
+                    let (channel, range) = widest_channel(colors);
+                    (range, i, channel)
+                })
+                .max_by_key(|&(range, i, _)| (range, std::cmp::Reverse(i)));
+
+            let (i, channel) = match widest {
+                Some((_, i, channel)) => (i, channel),
+                None => break,
+            };
+
+            let mut colors = boxes.swap_remove(i);
+            colors.sort_unstable_by_key(|(color, _)| (color[channel], *color));
+
+            let half = colors.iter().map(|(_, bucket)| bucket.count).sum::<u64>() / 2;
+            let mut seen = 0;
+            let split = colors
+                .iter()
+                .position(|(_, bucket)| {
+                    seen += bucket.count;
+                    seen > half
+                })
+                .unwrap_or(0)
+                .clamp(1, colors.len() - 1);
+
+            let upper = colors.split_off(split);
+            boxes.push(colors);
+            boxes.push(upper);
+        }
+
+        let mut colors = boxes
+            .iter()
+            .map(|colors| average(colors))
+            .collect::<Vec<_>>();
+        colors.sort_unstable();
+        colors.dedup();
+
+        Self { colors }
+    }
+
+    /// Function that returns the index of the color of the palette closest to the given color.
+    pub fn nearest(&self, color: [u8; 3]) -> usize {
+        self.colors
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, candidate)| {
+                (0..3)
+                    .map(|c| (candidate[c] as i32 - color[c] as i32).pow(2))
+                    .sum::<i32>()
+            })
+            .map_or(0, |(i, _)| i)
+    }
+}
+
+/// Function that returns the channel with the widest range of values in a box of colors, and that range.
+fn widest_channel(colors: &[([u8; 3], Bucket)]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let values = colors.iter().map(|(color, _)| color[channel]);
+            let range = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+            (channel, range)
+        })
+        .max_by_key(|&(channel, range)| (range, std::cmp::Reverse(channel)))
+        .unwrap_or((0, 0))
+}
+
+/// Function that returns the average color of the pixels in a box.
+fn average(colors: &[([u8; 3], Bucket)]) -> [u8; 3] {
+    let total = colors
+        .iter()
+        .map(|(_, bucket)| bucket.count)
+        .sum::<u64>()
+        .max(1);
+
+    [0, 1, 2].map(|c| {
+        let sum = colors.iter().map(|(_, bucket)| bucket.sum[c]).sum::<u64>();
+        ((sum + total / 2) / total) as u8
+    })
+}
+
+/// Function that drops the low bits of a color so similar colors share a histogram entry.
+fn reduce(color: [u8; 3]) -> [u8; 3] {
+    color.map(|c| c >> (8 - HISTOGRAM_BITS))
+}
+
+/// Function that scales a reduced color back to the full 0-255 range.
+fn expand(color: [u8; 3]) -> [u8; 3] {
+    color.map(|c| (c << (8 - HISTOGRAM_BITS)) | (c >> (2 * HISTOGRAM_BITS - 8)))
+}
+
+/// Function that writes a run of the same sixel character, compressed when it is long enough to save bytes.
+fn write_run(out: &mut Vec<u8>, character: u8, count: usize) {
+    match count {
+        0 => {}
+        1..=3 => out.extend(std::iter::repeat_n(character, count)),
+        _ => {
+            let _ = write!(out, "!{count}");
+            out.push(character);
+        }
+    }
+}
+
+/// Function that encodes an image as a sixel sequence, quantizing it to a palette of at most `max_colors` colors.
+pub fn encode(image: &RgbImage, max_colors: usize) -> Vec<u8> {
+    let palette = Palette::median_cut(image, max_colors);
+    encode_with_palette(image, &palette)
+}
+
+/// Function that encodes an image as a sixel sequence using the given palette. Each pixel is drawn with the closest
+/// color of the palette.
+pub fn encode_with_palette(image: &RgbImage, palette: &Palette) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    let mut out = Vec::new();
+
+    // Pixel aspect ratio 1:1 and pixels without a color left untouched.
+    let _ = write!(out, "\x1bP0;1;0q\"1;1;{width};{height}");
+
+    palette.colors.iter().enumerate().for_each(|(i, color)| {
+        let [red, green, blue] = color.map(|c| (c as u32 * 100 + 127) / 255);
+        let _ = write!(out, "#{i};2;{red};{green};{blue}");
+    });
+
+    let mut nearest = HashMap::new();
+    let indexes = image
+        .pixels()
+        .map(|pixel| {
+            *nearest
+                .entry(pixel.0)
+                .or_insert_with(|| palette.nearest(pixel.0))
+        })
+        .collect::<Vec<usize>>();
+
+    (0..height).step_by(BAND_HEIGHT as usize).for_each(|top| {
+        if top > 0 {
+            out.push(b'-');
+        }
+
+        // Sixel bits of each color used in the band, indexed by column.
+        let mut band: Vec<Option<Vec<u8>>> = vec![None; palette.colors.len()];
+        (top..(top + BAND_HEIGHT).min(height)).for_each(|y| {
+            (0..width).for_each(|x| {
+                let color = indexes[(y * width + x) as usize];
+                if let Some(columns) = band.get_mut(color) {
+                    columns.get_or_insert_with(|| vec![0; width as usize])[x as usize] |=
+                        1 << (y - top);
+                }
+            })
+        });
+
+        band.iter()
+            .enumerate()
+            .filter_map(|(color, columns)| columns.as_ref().map(|columns| (color, columns)))
+            .enumerate()
+            .for_each(|(n, (color, columns))| {
+                if n > 0 {
+                    out.push(b'$');
+                }
+                let _ = write!(out, "#{color}");
+
+                // Trailing empty sixels don't need to be sent.
+                let used = columns
+                    .iter()
+                    .rposition(|&bits| bits != 0)
+                    .map_or(0, |i| i + 1);
+
+                let (mut character, mut count) = (0, 0);
+                columns[..used].iter().for_each(|&bits| {
+                    let next = b'?' + bits;
+                    if next != character {
+                        write_run(&mut out, character, count);
+                        (character, count) = (next, 0);
+                    }
+                    count += 1;
+                });
+                write_run(&mut out, character, count);
+            });
+    });
+
+    out.extend_from_slice(b"\x1b\\");
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    const BLACK: [u8; 3] = [0, 0, 0];
+    const WHITE: [u8; 3] = [255, 255, 255];
+
+    fn black_and_white() -> Palette {
+        Palette {
+            colors: vec![BLACK, WHITE],
+        }
+    }
+
+    fn encoded(image: &RgbImage, palette: &Palette) -> String {
+        String::from_utf8(encode_with_palette(image, palette)).unwrap()
+    }
+
+    #[test]
+    fn encodes_a_single_pixel() {
+        let image = RgbImage::from_pixel(1, 1, Rgb([255, 0, 0]));
+        let palette = Palette {
+            colors: vec![[255, 0, 0]],
+        };
+
+        assert_eq!(
+            encoded(&image, &palette),
+            "\x1bP0;1;0q\"1;1;1;1#0;2;100;0;0#0@\x1b\\"
+        );
+    }
+
+    #[test]
+    fn encodes_the_bits_of_each_color_of_a_band() {
+        // The first column is black above white, the second one alternates starting with black.
+        let image = RgbImage::from_fn(2, 6, |x, y| match (x, y) {
+            (0, 0..3) => Rgb(BLACK),
+            (0, _) => Rgb(WHITE),
+            (_, y) if y % 2 == 0 => Rgb(BLACK),
+            _ => Rgb(WHITE),
+        });
+
+        assert_eq!(
+            encoded(&image, &black_and_white()),
+            "\x1bP0;1;0q\"1;1;2;6#0;2;0;0;0#1;2;100;100;100#0FT$#1wi\x1b\\"
+        );
+    }
+
+    #[test]
+    fn leaves_out_trailing_empty_sixels() {
+        let image = RgbImage::from_fn(3, 1, |x, _| match x {
+            0 | 1 => Rgb(BLACK),
+            _ => Rgb(WHITE),
+        });
+
+        assert_eq!(
+            encoded(&image, &black_and_white()),
+            "\x1bP0;1;0q\"1;1;3;1#0;2;0;0;0#1;2;100;100;100#0@@$#1??@\x1b\\"
+        );
+    }
+
+    #[test]
+    fn separates_bands() {
+        let image = RgbImage::from_pixel(1, 7, Rgb(WHITE));
+        let palette = Palette {
+            colors: vec![WHITE],
+        };
+
+        assert_eq!(
+            encoded(&image, &palette),
+            "\x1bP0;1;0q\"1;1;1;7#0;2;100;100;100#0~-#0@\x1b\\"
+        );
+    }
+
+    #[test]
+    fn compresses_long_runs() {
+        let palette = Palette {
+            colors: vec![WHITE],
+        };
+
+        assert_eq!(
+            encoded(&RgbImage::from_pixel(10, 1, Rgb(WHITE)), &palette),
+            "\x1bP0;1;0q\"1;1;10;1#0;2;100;100;100#0!10@\x1b\\"
+        );
+        assert_eq!(
+            encoded(&RgbImage::from_pixel(3, 1, Rgb(WHITE)), &palette),
+            "\x1bP0;1;0q\"1;1;3;1#0;2;100;100;100#0@@@\x1b\\"
+        );
+    }
+
+    #[test]
+    fn median_cut_is_deterministic() {
+        let image = RgbImage::from_fn(16, 16, |x, y| Rgb([(x * 16) as u8, (y * 16) as u8, 128]));
+
+        assert_eq!(
+            Palette::median_cut(&image, 8),
+            Palette::median_cut(&image, 8)
+        );
+        assert_eq!(encode(&image, 8), encode(&image, 8));
+    }
+
+    #[test]
+    fn median_cut_respects_max_colors() {
+        let image = RgbImage::from_fn(16, 16, |x, y| Rgb([(x * 16) as u8, (y * 16) as u8, 128]));
+
+        [1, 2, 5, 16].iter().for_each(|&max_colors| {
+            assert!(Palette::median_cut(&image, max_colors).colors.len() <= max_colors);
+        });
+        assert_eq!(Palette::median_cut(&image, 1024).colors.len(), 256);
+    }
+
+    #[test]
+    fn median_cut_keeps_the_colors_of_small_images() {
+        let image = RgbImage::from_fn(2, 1, |x, _| match x {
+            0 => Rgb(BLACK),
+            _ => Rgb(WHITE),
+        });
+
+        assert_eq!(Palette::median_cut(&image, 16), black_and_white());
+    }
+}
@@ -0,0 +1,120 @@
+//! Module that implements the counters that show how much is drawn in the terminal.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// Struct that represents the counters of a renderer. They are atomic so they can be read from another thread
+/// (e.g. after the feed ends) through an `Arc`.
+#[derive(Debug)]
+pub struct RenderStats {
+    frames: AtomicU64,
+    bytes: AtomicU64,
+    dropped: AtomicU64,
+    created: Instant,
+    /// Nanoseconds between `created` and the first and the last frame drawn.
+    first_frame: AtomicU64,
+    last_frame: AtomicU64,
+}
+
+impl Default for RenderStats {
+    fn default() -> Self {
+        Self {
+            frames: AtomicU64::default(),
+            bytes: AtomicU64::default(),
+            dropped: AtomicU64::default(),
+            created: Instant::now(),
+            first_frame: AtomicU64::default(),
+            last_frame: AtomicU64::default(),
+        }
+    }
+}
+
+impl RenderStats {
+    /// Function that counts a frame that was drawn with the given number of bytes.
+    pub fn record_frame(&self, bytes: usize) {
+        let elapsed = self.created.elapsed().as_nanos() as u64;
+
+        if self.frames.fetch_add(1, Ordering::Relaxed) == 0 {
+            self.first_frame.store(elapsed, Ordering::Relaxed);
+        }
+        self.last_frame.store(elapsed, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Function that counts frames that were dropped because the terminal was still busy with the previous ones.
+    pub fn record_dropped(&self, frames: u64) {
+        self.dropped.fetch_add(frames, Ordering::Relaxed);
+    }
+
+    /// Function that returns how many frames were drawn.
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// Function that returns how many frames were dropped.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Function that returns how many bytes were sent to the terminal on average for each frame.
+    pub fn bytes_per_frame(&self) -> f64 {
+        match self.frames() {
+            0 => 0.,
+            frames => self.bytes.load(Ordering::Relaxed) as f64 / frames as f64,
+        }
+    }
+
+    /// Function that returns how many frames were drawn per second, between the first and the last one.
+    pub fn frames_per_second(&self) -> f64 {
+        let nanos = self
+            .last_frame
+            .load(Ordering::Relaxed)
+            .saturating_sub(self.first_frame.load(Ordering::Relaxed));
+
+        match (self.frames(), nanos) {
+            (0 | 1, _) | (_, 0) => 0.,
+            (frames, nanos) => (frames - 1) as f64 * 1e9 / nanos as f64,
+        }
+    }
+}
+
+impl fmt::Display for RenderStats {
+    /// Function that writes a one line summary of the counters, shown when a feed ends.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Drew {} frames ({:.1} per second) and dropped {}, {:.0} bytes per frame on average.",
+            self.frames(),
+            self.frames_per_second(),
+            self.dropped(),
+            self.bytes_per_frame()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_the_counters() {
+        let stats = RenderStats::default();
+        assert_eq!(
+            stats.to_string(),
+            "Drew 0 frames (0.0 per second) and dropped 0, 0 bytes per frame on average."
+        );
+
+        stats.record_frame(100);
+        stats.record_frame(300);
+        stats.record_dropped(2);
+        assert_eq!(stats.frames(), 2);
+        assert_eq!(stats.dropped(), 2);
+        assert_eq!(stats.bytes_per_frame(), 200.);
+        assert!(
+            stats
+                .to_string()
+                .ends_with("dropped 2, 200 bytes per frame on average.")
+        );
+    }
+}
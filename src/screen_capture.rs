@@ -1,32 +1,416 @@
 //! Module that communicates with the screen sharing API and converts it into a feed.
 
-use crate::{FILTER, feed::Feed};
-use bincode::config::{self, Configuration};
-use image::{DynamicImage, ImageBuffer, Rgb};
-use std::{error::Error, time::Duration};
+use crate::{
+    FILTER,
+    config::{FeedConfig, parse_geometry},
+    feed::{Feed, FeedConstructor, FeedSource},
+};
+use image::{DynamicImage, ImageBuffer, Rgb, RgbaImage};
+use std::error::Error;
+use std::str::FromStr;
 use xcap::Monitor;
 
+/// Enum that represents how a monitor is chosen.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub enum MonitorSelector {
+    /// The primary monitor, or the first one if none is marked as primary.
+    #[default]
+    Primary,
+    /// The monitor at the given position of `Screen::list_monitors`.
+    Index(usize),
+    /// The monitor with the given name.
+    Name(String),
+}
+
+impl FromStr for MonitorSelector {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    /// Function that parses `primary`, the index of a monitor or its name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err("The monitor selector is empty.".into()),
+            "primary" => Ok(Self::Primary),
+            _ => match s.parse() {
+                Ok(index) => Ok(Self::Index(index)),
+                Err(_) => Ok(Self::Name(s.to_string())),
+            },
+        }
+    }
+}
+
+/// Struct that represents a rectangle in the coordinates of a monitor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Region {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    /// Function that parses a region written as `WIDTHxHEIGHT+X+Y`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid region `{s}`, expected WIDTHxHEIGHT+X+Y.");
+
+        let mut parts = s.split('+');
+        let (width, height) = parse_geometry(parts.next().unwrap_or_default())?;
+        let mut offset = || -> Result<u32, Box<dyn Error + Send + Sync>> {
+            Ok(parts
+                .next()
+                .ok_or_else(invalid)?
+                .parse()
+                .map_err(|_| invalid())?)
+        };
+        let (x, y) = (offset()?, offset()?);
+
+        match parts.next() {
+            Some(_) => Err(invalid().into()),
+            None => Ok(Self {
+                x,
+                y,
+                width,
+                height,
+            }),
+        }
+    }
+}
+
+impl Region {
+    /// Function that returns whether the region is not empty and lies inside a monitor of the given size.
+    pub fn fits_in(&self, width: u32, height: u32) -> bool {
+        self.width != 0
+            && self.height != 0
+            && self
+                .x
+                .checked_add(self.width)
+                .is_some_and(|right| right <= width)
+            && self
+                .y
+                .checked_add(self.height)
+                .is_some_and(|bottom| bottom <= height)
+    }
+}
+
+/// Enum that represents what part of the desktop is captured.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScreenArea {
+    /// A whole monitor.
+    Monitor(MonitorSelector),
+    /// A rectangular region of a monitor.
+    Region(MonitorSelector, Region),
+    /// Every monitor stitched together in one canvas following their desktop layout.
+    AllMonitors,
+}
+
+impl Default for ScreenArea {
+    fn default() -> Self {
+        Self::Monitor(MonitorSelector::default())
+    }
+}
+
+impl FromStr for ScreenArea {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    /// Function that parses `all` or a monitor selector, optionally followed by `@WIDTHxHEIGHT+X+Y` to capture a
+    /// region of the monitor (e.g. `1@640x480+0+0`, or `@640x480+0+0` for the primary monitor).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let monitor = |selector: &str| match selector {
+            "" => Ok(MonitorSelector::Primary),
+            selector => selector.parse(),
+        };
+
+        match s.split_once('@') {
+            _ if s == "all" => Ok(Self::AllMonitors),
+            Some((selector, region)) => Ok(Self::Region(monitor(selector)?, region.parse()?)),
+            None => Ok(Self::Monitor(monitor(s)?)),
+        }
+    }
+}
+
+/// Bounds captured screens and windows are scaled to fit in, preserving their aspect ratio.
+pub const MAX_CAPTURE_SIZE: (u32, u32) = (640, 320);
+
+/// Struct that represents the information of an available monitor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MonitorInfo {
+    pub index: usize,
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub is_primary: bool,
+}
+
+/// Struct that represents the screen being shared.
 pub struct Screen {
-    pub monitor: Monitor,
+    pub monitors: Vec<Monitor>,
+    pub region: Option<Region>,
 }
 
-impl Feed for Screen {
-    const FRAME_RATE: u32 = 200;
-    const ENCODE_CONFIG: Configuration = config::standard();
-    const TIMEOUT_DURATION: Duration = Duration::from_secs(1);
+impl Screen {
+    /// Function that lists the monitors available to be shared.
+    pub fn list_monitors() -> Result<Vec<MonitorInfo>, Box<dyn Error + Send + Sync>> {
+        Monitor::all()?
+            .iter()
+            .enumerate()
+            .map(|(index, monitor)| Self::monitor_info(index, monitor))
+            .collect()
+    }
+
+    /// Function that creates a screen feed that captures the given area of the desktop.
+    pub fn with_area(area: ScreenArea) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let monitors = Monitor::all()?;
+        if monitors.is_empty() {
+            return Err("No monitor is available to be shared.".into());
+        }
+
+        match area {
+            ScreenArea::Monitor(selector) => Ok(Self {
+                monitors: vec![Self::select(monitors, &selector)?],
+                region: None,
+            }),
+            ScreenArea::Region(selector, region) => {
+                let monitor = Self::select(monitors, &selector)?;
+                let (width, height) = (monitor.width()?, monitor.height()?);
+
+                if !region.fits_in(width, height) {
+                    return Err(format!(
+                        "Region {}x{}+{}+{} is outside of the {width}x{height} monitor.",
+                        region.width, region.height, region.x, region.y
+                    )
+                    .into());
+                }
 
-    fn new() -> Result<Self, Box<dyn Error + Send + Sync>> {
-        Ok(Self {
-            monitor: Monitor::all()?[0].clone(),
+                Ok(Self {
+                    monitors: vec![monitor],
+                    region: Some(region),
+                })
+            }
+            ScreenArea::AllMonitors => Ok(Self {
+                monitors,
+                region: None,
+            }),
+        }
+    }
+
+    /// Function that picks a monitor using a selector.
+    fn select(
+        monitors: Vec<Monitor>,
+        selector: &MonitorSelector,
+    ) -> Result<Monitor, Box<dyn Error + Send + Sync>> {
+        let mut found = None;
+
+        for (index, monitor) in monitors.iter().enumerate() {
+            let matches = match selector {
+                MonitorSelector::Primary => monitor.is_primary()?,
+                MonitorSelector::Index(i) => index == *i,
+                MonitorSelector::Name(name) => monitor.name()? == *name,
+            };
+            if matches {
+                found = Some(monitor.clone());
+                break;
+            }
+        }
+
+        match (found, selector) {
+            (Some(monitor), _) => Ok(monitor),
+            (None, MonitorSelector::Primary) => Ok(monitors[0].clone()),
+            (None, MonitorSelector::Index(i)) => Err(format!(
+                "There is no monitor with index {i} ({} available).",
+                monitors.len()
+            )
+            .into()),
+            (None, MonitorSelector::Name(name)) => {
+                Err(format!("There is no monitor named `{name}`.").into())
+            }
+        }
+    }
+
+    /// Function that gathers the information of a monitor.
+    fn monitor_info(
+        index: usize,
+        monitor: &Monitor,
+    ) -> Result<MonitorInfo, Box<dyn Error + Send + Sync>> {
+        Ok(MonitorInfo {
+            index,
+            name: monitor.name()?,
+            x: monitor.x()?,
+            y: monitor.y()?,
+            width: monitor.width()?,
+            height: monitor.height()?,
+            is_primary: monitor.is_primary()?,
         })
     }
 
+    /// Function that stitches every monitor into one canvas already scaled to fit `MAX_CAPTURE_SIZE`.
+    fn capture_all(&self) -> Result<RgbaImage, Box<dyn Error + Send + Sync>> {
+        let layouts = self
+            .monitors
+            .iter()
+            .enumerate()
+            .map(|(i, monitor)| Self::monitor_info(i, monitor))
+            .collect::<Result<Vec<MonitorInfo>, _>>()?;
+
+        let left = layouts.iter().map(|m| m.x).min().unwrap_or(0);
+        let top = layouts.iter().map(|m| m.y).min().unwrap_or(0);
+        let right = layouts
+            .iter()
+            .map(|m| m.x + m.width as i32)
+            .max()
+            .unwrap_or(0);
+        let bottom = layouts
+            .iter()
+            .map(|m| m.y + m.height as i32)
+            .max()
+            .unwrap_or(0);
+
+        let desktop = ((right - left) as u32, (bottom - top) as u32);
+        let (canvas_width, canvas_height) = fit_size(desktop, MAX_CAPTURE_SIZE);
+        let scale = canvas_width as f64 / desktop.0 as f64;
+
+        let mut canvas = RgbaImage::new(canvas_width, canvas_height);
+
+        for (monitor, layout) in self.monitors.iter().zip(layouts.iter()) {
+            let scaled = |v: f64| (v * scale).round() as u32;
+            let (width, height) = (
+                scaled(layout.width as f64).max(1),
+                scaled(layout.height as f64).max(1),
+            );
+
+            let image = image::imageops::resize(&monitor.capture_image()?, width, height, FILTER);
+            image::imageops::replace(
+                &mut canvas,
+                &image,
+                scaled((layout.x - left) as f64) as i64,
+                scaled((layout.y - top) as f64) as i64,
+            );
+        }
+
+        Ok(canvas)
+    }
+}
+
+impl Feed for Screen {
+    fn default_config() -> FeedConfig {
+        FeedConfig {
+            frame_rate: 200,
+            ..FeedConfig::default()
+        }
+    }
+}
+
+impl FeedConstructor for Screen {
+    fn new(_config: &FeedConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Self::with_area(ScreenArea::default())
+    }
+}
+
+impl FeedSource for Screen {
     fn get_frame_rgb(
         &mut self,
     ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, Box<dyn Error + Send + Sync>> {
-        let frame = image::imageops::resize(&self.monitor.capture_image()?, 640, 320, FILTER);
+        let frame = match (&self.monitors[..], self.region) {
+            ([monitor], Some(region)) => {
+                monitor.capture_region(region.x, region.y, region.width, region.height)?
+            }
+            ([monitor], None) => monitor.capture_image()?,
+            _ => self.capture_all()?,
+        };
+
+        let (width, height) = fit_size(frame.dimensions(), MAX_CAPTURE_SIZE);
+        let frame = image::imageops::resize(&frame, width, height, FILTER);
         let rgb_image = DynamicImage::ImageRgba8(frame).into_rgb8();
 
         Ok(rgb_image)
     }
 }
+
+/// Function that returns the largest size that fits in `bounds` while keeping the aspect ratio of `size`.
+pub fn fit_size(size: (u32, u32), bounds: (u32, u32)) -> (u32, u32) {
+    let (width, height) = (size.0.max(1) as f64, size.1.max(1) as f64);
+    let scale = (bounds.0 as f64 / width).min(bounds.1 as f64 / height);
+
+    (
+        ((width * scale).round() as u32).max(1),
+        ((height * scale).round() as u32).max(1),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_regions_against_the_monitor() {
+        let region = |x, y, width, height| Region {
+            x,
+            y,
+            width,
+            height,
+        };
+
+        assert!(region(0, 0, 640, 480).fits_in(640, 480));
+        assert!(region(10, 20, 630, 460).fits_in(640, 480));
+        assert!(!region(11, 0, 630, 480).fits_in(640, 480));
+        assert!(!region(0, 0, 0, 480).fits_in(640, 480));
+        // Offsets that overflow when added to the size are rejected instead of wrapping around.
+        assert!(!region(u32::MAX, 0, 2, 480).fits_in(640, 480));
+        assert!(!region(0, u32::MAX, 640, 1).fits_in(640, 480));
+    }
+
+    #[test]
+    fn parses_screen_areas() {
+        assert_eq!(
+            "all".parse::<ScreenArea>().unwrap(),
+            ScreenArea::AllMonitors
+        );
+        assert_eq!(
+            "1".parse::<ScreenArea>().unwrap(),
+            ScreenArea::Monitor(MonitorSelector::Index(1))
+        );
+        assert_eq!(
+            "HDMI-1".parse::<ScreenArea>().unwrap(),
+            ScreenArea::Monitor(MonitorSelector::Name("HDMI-1".to_string()))
+        );
+        assert_eq!(
+            "@640x480+10+20".parse::<ScreenArea>().unwrap(),
+            ScreenArea::Region(
+                MonitorSelector::Primary,
+                Region {
+                    x: 10,
+                    y: 20,
+                    width: 640,
+                    height: 480
+                }
+            )
+        );
+        assert_eq!(
+            "primary@1x2+3+4".parse::<ScreenArea>().unwrap(),
+            ScreenArea::Region(
+                MonitorSelector::Primary,
+                Region {
+                    x: 3,
+                    y: 4,
+                    width: 1,
+                    height: 2
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_regions() {
+        [
+            "640x480",
+            "640x480+1",
+            "640x480+1+2+3",
+            "640x480+a+1",
+            "640+1+2",
+        ]
+        .iter()
+        .for_each(|region| assert!(region.parse::<Region>().is_err(), "{region}"));
+    }
+}
@@ -0,0 +1,47 @@
+//! Module that implements the handle used to replace the source of a running feed.
+
+use std::sync::{Arc, Mutex};
+
+/// Struct that represents a handle to swap the source of a running feed. The new source is created by whoever
+/// holds the handle, so the feed keeps sending frames from the old source until the new one is ready.
+pub struct SourceSwitch<T> {
+    pending: Arc<Mutex<Option<T>>>,
+}
+
+impl<T> Clone for SourceSwitch<T> {
+    fn clone(&self) -> Self {
+        Self {
+            pending: self.pending.clone(),
+        }
+    }
+}
+
+impl<T> Default for SourceSwitch<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> SourceSwitch<T> {
+    /// Function that creates a switch without any pending source.
+    pub fn new() -> Self {
+        Self {
+            pending: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Function that asks the feed to move to a new source. A source that wasn't picked up yet is replaced.
+    pub fn switch_to(&self, source: T) {
+        if let Ok(mut pending) = self.pending.lock() {
+            *pending = Some(source);
+        }
+    }
+
+    /// Function that takes the pending source, if there is one.
+    pub fn take(&self) -> Option<T> {
+        match self.pending.lock() {
+            Ok(mut pending) => pending.take(),
+            Err(_) => None,
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! Module that generates deterministic synthetic frames and converts them into a feed.
+//! Useful when there is no camera or display available (e.g. CI or containers).
+
+use crate::config::FeedConfig;
+use crate::feed::{Feed, FeedConstructor, FeedSource};
+use image::{ImageBuffer, Rgb};
+use std::error::Error;
+use std::str::FromStr;
+
+/// Seed used when the test pattern is created through `FeedConstructor::new`.
+pub const DEFAULT_SEED: u64 = 0x5EED_CAFE_F00D_BEEF;
+
+/// Size of the frames generated by the test pattern.
+pub const PATTERN_SIZE: (u32, u32) = (640, 480);
+
+/// Enum that represents the background drawn by the test pattern.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Pattern {
+    /// SMPTE color bars.
+    #[default]
+    ColorBars,
+    /// Diagonal gradient that moves every frame.
+    Gradient,
+}
+
+impl FromStr for Pattern {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bars" => Ok(Self::ColorBars),
+            "gradient" => Ok(Self::Gradient),
+            _ => Err(format!("Invalid pattern `{s}`, expected bars or gradient.").into()),
+        }
+    }
+}
+
+/// Struct that represents a synthetic feed source. The background, a bouncing box and a frame
+/// counter are drawn on every frame and the same seed always produces the same sequence of frames.
+pub struct TestPattern {
+    pub pattern: Pattern,
+    pub frame_count: u64,
+    phase: u32,
+    box_position: (i32, i32),
+    box_velocity: (i32, i32),
+    box_color: Rgb<u8>,
+}
+
+impl TestPattern {
+    /// Size of the side of the bouncing box.
+    const BOX_SIZE: u32 = 64;
+
+    /// Scale applied to the glyphs of the frame counter.
+    const COUNTER_SCALE: u32 = 6;
+
+    /// Function that creates a new test pattern from a background and a seed.
+    pub fn with_seed(pattern: Pattern, seed: u64) -> Self {
+        let mut rng = XorShift::new(seed);
+        let (width, height) = PATTERN_SIZE;
+
+        let box_position = (
+            rng.next_in(width - Self::BOX_SIZE) as i32,
+            rng.next_in(height - Self::BOX_SIZE) as i32,
+        );
+        let box_velocity = (
+            (rng.next_in(6) as i32 + 2) * if rng.next_in(2) == 0 { 1 } else { -1 },
+            (rng.next_in(6) as i32 + 2) * if rng.next_in(2) == 0 { 1 } else { -1 },
+        );
+        let box_color = Rgb([
+            rng.next_in(256) as u8,
+            rng.next_in(256) as u8,
+            rng.next_in(256) as u8,
+        ]);
+
+        Self {
+            pattern,
+            frame_count: 0,
+            phase: rng.next_in(width),
+            box_position,
+            box_velocity,
+            box_color,
+        }
+    }
+
+    /// Function that draws the background of the current frame.
+    fn background(&self) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        let (width, height) = PATTERN_SIZE;
+
+        match self.pattern {
+            Pattern::ColorBars => ImageBuffer::from_fn(width, height, color_bars_pixel),
+            Pattern::Gradient => {
+                // The gradient repeats every 512 steps of the shift (it is halved for green), so the frame count is
+                // reduced first and never overflows.
+                let shift = self.phase + (self.frame_count % 128) as u32 * 4;
+                ImageBuffer::from_fn(width, height, |x, y| {
+                    Rgb([
+                        ((x + shift) % 256) as u8,
+                        ((y + shift / 2) % 256) as u8,
+                        (((x + y) / 4 + shift) % 256) as u8,
+                    ])
+                })
+            }
+        }
+    }
+
+    /// Function that moves the bouncing box one step, reversing its direction when it hits an edge.
+    fn step_box(&mut self) {
+        let limits = (
+            (PATTERN_SIZE.0 - Self::BOX_SIZE) as i32,
+            (PATTERN_SIZE.1 - Self::BOX_SIZE) as i32,
+        );
+
+        let (x, vx) = bounce(self.box_position.0, self.box_velocity.0, limits.0);
+        let (y, vy) = bounce(self.box_position.1, self.box_velocity.1, limits.1);
+
+        self.box_position = (x, y);
+        self.box_velocity = (vx, vy);
+    }
+
+    /// Function that draws the bouncing box on the frame.
+    fn draw_box(&self, image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>) {
+        let (x0, y0) = (self.box_position.0 as u32, self.box_position.1 as u32);
+
+        (y0..y0 + Self::BOX_SIZE).for_each(|y| {
+            (x0..x0 + Self::BOX_SIZE).for_each(|x| image.put_pixel(x, y, self.box_color));
+        });
+    }
+
+    /// Function that draws the frame counter on the top left corner of the frame.
+    fn draw_counter(&self, image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>) {
+        let scale = Self::COUNTER_SCALE;
+        let digits = self.frame_count.to_string();
+
+        digits.bytes().enumerate().for_each(|(i, digit)| {
+            let glyph = DIGITS[(digit - b'0') as usize];
+            let offset_x = scale + i as u32 * 4 * scale;
+
+            (0..5u32).for_each(|row| {
+                (0..3u32).for_each(|column| {
+                    let lit = glyph[row as usize] & (0b100 >> column) != 0;
+                    let color = if lit {
+                        Rgb([255, 255, 255])
+                    } else {
+                        Rgb([0, 0, 0])
+                    };
+
+                    (0..scale).for_each(|dy| {
+                        (0..scale).for_each(|dx| {
+                            let (x, y) = (offset_x + column * scale + dx, scale + row * scale + dy);
+                            if x < image.width() && y < image.height() {
+                                image.put_pixel(x, y, color);
+                            }
+                        });
+                    });
+                });
+            });
+        });
+    }
+}
+
+impl FromStr for TestPattern {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    /// Function that parses a test pattern written as `PATTERN`, `PATTERN@SEED` or `@SEED`. The default pattern and
+    /// seed are used for the parts that are left out.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pattern, seed) = match s.split_once('@') {
+            Some((pattern, seed)) => (
+                pattern,
+                seed.parse()
+                    .map_err(|_| format!("Invalid seed `{seed}`, expected a number."))?,
+            ),
+            None => (s, DEFAULT_SEED),
+        };
+
+        let pattern = match pattern {
+            "" => Pattern::default(),
+            pattern => pattern.parse()?,
+        };
+
+        Ok(Self::with_seed(pattern, seed))
+    }
+}
+
+impl Feed for TestPattern {
+    fn default_config() -> FeedConfig {
+        FeedConfig {
+            frame_rate: 60,
+            ..FeedConfig::default()
+        }
+    }
+}
+
+impl FeedConstructor for TestPattern {
+    fn new(_config: &FeedConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self::with_seed(Pattern::default(), DEFAULT_SEED))
+    }
+}
+
+impl FeedSource for TestPattern {
+    fn get_frame_rgb(
+        &mut self,
+    ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, Box<dyn Error + Send + Sync>> {
+        let mut image = self.background();
+        self.draw_box(&mut image);
+        self.draw_counter(&mut image);
+
+        self.step_box();
+        self.frame_count += 1;
+
+        Ok(image)
+    }
+}
+
+/// Function that returns the color of the SMPTE color bars at a given coordinate.
+fn color_bars_pixel(x: u32, y: u32) -> Rgb<u8> {
+    const TOP: [[u8; 3]; 7] = [
+        [191, 191, 191],
+        [191, 191, 0],
+        [0, 191, 191],
+        [0, 191, 0],
+        [191, 0, 191],
+        [191, 0, 0],
+        [0, 0, 191],
+    ];
+    const MIDDLE: [[u8; 3]; 7] = [
+        [0, 0, 191],
+        [19, 19, 19],
+        [191, 0, 191],
+        [19, 19, 19],
+        [0, 191, 191],
+        [19, 19, 19],
+        [191, 191, 191],
+    ];
+    const BOTTOM: [[u8; 3]; 4] = [[0, 33, 76], [255, 255, 255], [50, 0, 106], [19, 19, 19]];
+
+    let (width, height) = PATTERN_SIZE;
+
+    if y < height * 2 / 3 {
+        Rgb(TOP[(x * 7 / width) as usize])
+    } else if y < height * 3 / 4 {
+        Rgb(MIDDLE[(x * 7 / width) as usize])
+    } else {
+        Rgb(BOTTOM[((x * 7 / width) as usize * 4 / 7).min(3)])
+    }
+}
+
+/// Function that moves a coordinate by a velocity, reflecting it inside `0..=limit`.
+fn bounce(position: i32, velocity: i32, limit: i32) -> (i32, i32) {
+    let next = position + velocity;
+
+    if next < 0 {
+        (-next, -velocity)
+    } else if next > limit {
+        (2 * limit - next, -velocity)
+    } else {
+        (next, velocity)
+    }
+}
+
+/// Glyphs for the digits of the frame counter, three bits wide and five rows tall.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// Struct that represents a small deterministic pseudo-random number generator.
+struct XorShift(u64);
+
+impl XorShift {
+    /// Function that creates a new generator. A zero seed is replaced because xorshift would only produce zeros.
+    fn new(seed: u64) -> Self {
+        Self(if seed == 0 { DEFAULT_SEED } else { seed })
+    }
+
+    /// Function that returns a number in `0..bound`.
+    fn next_in(&mut self, bound: u32) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        (self.0 % bound as u64) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_the_gradient_for_any_frame_count() {
+        let mut late = TestPattern::with_seed(Pattern::Gradient, DEFAULT_SEED);
+        late.frame_count = u64::MAX;
+        let mut early = TestPattern::with_seed(Pattern::Gradient, DEFAULT_SEED);
+        early.frame_count = u64::MAX % 128;
+
+        assert!(late.background() == early.background());
+    }
+
+    #[test]
+    fn parses_pattern_and_seed() {
+        let frame = |pattern: &str| {
+            pattern
+                .parse::<TestPattern>()
+                .unwrap()
+                .get_frame_rgb()
+                .unwrap()
+        };
+
+        assert_eq!(
+            "gradient".parse::<TestPattern>().unwrap().pattern,
+            Pattern::Gradient
+        );
+        assert_eq!(
+            "@7".parse::<TestPattern>().unwrap().pattern,
+            Pattern::ColorBars
+        );
+        assert_eq!(frame("bars@7"), frame("@7"));
+        assert_ne!(frame("@7"), frame("@8"));
+        assert!("stripes".parse::<TestPattern>().is_err());
+        assert!("bars@x".parse::<TestPattern>().is_err());
+    }
+}
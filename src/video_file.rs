@@ -0,0 +1,455 @@
+//! Module that decodes prerecorded video files (YUV4MPEG2 or raw `rgb24`) and converts them into a feed.
+
+use crate::feed::{Feed, FeedSource};
+use image::{ImageBuffer, Rgb, RgbImage};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom},
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// Largest frame, in bytes of `rgb24`, that is read (a 16K frame). Bigger sizes are almost surely a mistake and
+/// would try to allocate a buffer the size of the whole memory.
+pub const MAX_FRAME_BYTES: usize = 15360 * 8640 * 3;
+
+/// Enum that represents the container of the video file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VideoFormat {
+    /// YUV4MPEG2 file, the geometry and frame rate are read from its header.
+    Y4m,
+    /// Headerless sequence of packed `rgb24` frames with the geometry and frame rate given up front.
+    RawRgb { width: u32, height: u32, fps: f64 },
+}
+
+/// Enum that represents how the chroma planes of a YUV4MPEG2 file are subsampled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Chroma {
+    C420,
+    C422,
+    C444,
+    Mono,
+}
+
+impl Chroma {
+    /// Function that parses the value of the `C` header parameter. Only 8-bit colorspaces are supported, so the
+    /// high bit depth ones (e.g. `420p10`) are rejected instead of being read with the wrong frame size.
+    fn parse(value: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        match value {
+            "420" | "420jpeg" | "420paldv" | "420mpeg2" => Ok(Self::C420),
+            "422" => Ok(Self::C422),
+            "444" => Ok(Self::C444),
+            "mono" => Ok(Self::Mono),
+            v => Err(format!("Unsupported Y4M colorspace: C{v}.").into()),
+        }
+    }
+
+    /// Function that returns the size of each chroma plane.
+    fn plane_size(&self, width: u32, height: u32) -> (u32, u32) {
+        match self {
+            Self::C420 => (width.div_ceil(2), height.div_ceil(2)),
+            Self::C422 => (width.div_ceil(2), height),
+            Self::C444 => (width, height),
+            Self::Mono => (0, 0),
+        }
+    }
+}
+
+/// Struct that represents the stream properties of a YUV4MPEG2 file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Y4mHeader {
+    pub width: u32,
+    pub height: u32,
+    pub fps: f64,
+    pub chroma: Chroma,
+}
+
+impl Y4mHeader {
+    /// Function that parses the first line of a YUV4MPEG2 file (without the trailing newline).
+    pub fn parse(line: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut parameters = line.split(' ');
+
+        if parameters.next() != Some("YUV4MPEG2") {
+            return Err("Not a YUV4MPEG2 file.".into());
+        }
+
+        let (mut width, mut height, mut fps, mut chroma) = (None, None, 25., Chroma::C420);
+
+        for parameter in parameters.filter(|p| !p.is_empty()) {
+            // Tags are one ASCII letter, parameters starting with anything else are unknown and skipped.
+            let Some((tag, value)) = parameter.split_at_checked(1) else {
+                continue;
+            };
+            match tag {
+                "W" => width = Some(value.parse::<u32>()?),
+                "H" => height = Some(value.parse::<u32>()?),
+                "F" => {
+                    let (numerator, denominator) =
+                        value.split_once(':').ok_or("Invalid Y4M frame rate.")?;
+                    let (numerator, denominator) =
+                        (numerator.parse::<f64>()?, denominator.parse::<f64>()?);
+                    if numerator <= 0. || denominator <= 0. {
+                        return Err("Invalid Y4M frame rate.".into());
+                    }
+                    fps = numerator / denominator;
+                }
+                "C" => chroma = Chroma::parse(value)?,
+                _ => {}
+            }
+        }
+
+        match (width, height) {
+            (Some(width), Some(height)) if width > 0 && height > 0 => {
+                raw_frame_bytes(width, height)?;
+
+                Ok(Self {
+                    width,
+                    height,
+                    fps,
+                    chroma,
+                })
+            }
+            _ => Err("Y4M header is missing the frame size.".into()),
+        }
+    }
+}
+
+/// Struct that represents a video file being played as a feed. Frames are paced to the frame rate of the file
+/// and, once the file ends, it either starts over or raises the end flag.
+pub struct VideoFile {
+    reader: BufReader<File>,
+    format: VideoFormat,
+    header: Option<Y4mHeader>,
+    data_start: u64,
+    frame_duration: Duration,
+    pacer: Pacer,
+    looping: bool,
+    end_flag: Arc<AtomicBool>,
+    last_frame: Option<ImageBuffer<Rgb<u8>, Vec<u8>>>,
+}
+
+impl VideoFile {
+    /// Function that opens a video file. When `looping` is false the `end_flag` is raised at the end of the file.
+    pub fn open(
+        path: impl AsRef<Path>,
+        format: VideoFormat,
+        looping: bool,
+        end_flag: Arc<AtomicBool>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let (header, fps) = match format {
+            VideoFormat::Y4m => {
+                let mut line = String::new();
+                reader.read_line(&mut line)?;
+                let header = Y4mHeader::parse(line.trim_end_matches('\n'))?;
+                (Some(header), header.fps)
+            }
+            VideoFormat::RawRgb { width, height, fps } => {
+                if width == 0 || height == 0 || fps <= 0. {
+                    return Err("Raw video needs a non-zero frame size and frame rate.".into());
+                }
+                raw_frame_bytes(width, height)?;
+                (None, fps)
+            }
+        };
+
+        Ok(Self {
+            data_start: reader.stream_position()?,
+            reader,
+            format,
+            header,
+            frame_duration: Duration::from_secs_f64(1. / fps),
+            pacer: Pacer::new(),
+            looping,
+            end_flag,
+            last_frame: None,
+        })
+    }
+
+    /// Function that opens a video file guessing the format from its extension (`.y4m` or `.rgb`/`.raw`).
+    /// Raw files need the geometry and frame rate since they have no header.
+    pub fn open_path(
+        path: impl AsRef<Path>,
+        raw_geometry: Option<(u32, u32, f64)>,
+        looping: bool,
+        end_flag: Arc<AtomicBool>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+
+        let format = match (extension.as_deref(), raw_geometry) {
+            (Some("y4m"), _) => VideoFormat::Y4m,
+            (_, Some((width, height, fps))) => VideoFormat::RawRgb { width, height, fps },
+            _ => return Err("Raw video files need the frame size and frame rate.".into()),
+        };
+
+        Self::open(path, format, looping, end_flag)
+    }
+
+    /// Function that returns the frame rate the file is played at.
+    pub fn fps(&self) -> f64 {
+        1. / self.frame_duration.as_secs_f64()
+    }
+
+    /// Function that reads the next frame of the file. Returns `None` at the end of the file.
+    fn read_frame(&mut self) -> Result<Option<RgbImage>, Box<dyn Error + Send + Sync>> {
+        match (self.format, self.header) {
+            (VideoFormat::Y4m, Some(header)) => {
+                let mut line = String::new();
+                if self.reader.read_line(&mut line)? == 0 {
+                    return Ok(None);
+                }
+                if !line.starts_with("FRAME") {
+                    return Err("Corrupted Y4M frame header.".into());
+                }
+
+                let (chroma_x, chroma_y) = header.chroma.plane_size(header.width, header.height);
+                let mut planes =
+                    vec![0u8; (header.width * header.height + 2 * chroma_x * chroma_y) as usize];
+
+                match read_exact_or_eof(&mut self.reader, &mut planes)? {
+                    true => Ok(Some(yuv_to_rgb(&header, &planes))),
+                    false => Ok(None),
+                }
+            }
+            (VideoFormat::RawRgb { width, height, .. }, _) => {
+                read_raw_rgb(&mut self.reader, width, height)
+            }
+            (VideoFormat::Y4m, None) => Err("Y4M file without header.".into()),
+        }
+    }
+}
+
+impl Feed for VideoFile {}
+
+impl FeedSource for VideoFile {
+    fn get_frame_rgb(
+        &mut self,
+    ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, Box<dyn Error + Send + Sync>> {
+        self.pacer.wait(self.frame_duration);
+
+        let frame = match self.read_frame()? {
+            Some(frame) => Some(frame),
+            None if self.looping => {
+                self.reader.seek(SeekFrom::Start(self.data_start))?;
+                self.read_frame()?
+            }
+            None => {
+                self.end_flag.store(true, Ordering::Release);
+                None
+            }
+        };
+
+        match frame {
+            Some(frame) => {
+                self.last_frame = Some(frame.clone());
+                Ok(frame)
+            }
+            None => self
+                .last_frame
+                .clone()
+                .ok_or_else(|| "The video file has no frames.".into()),
+        }
+    }
+}
+
+/// Struct that blocks the caller until the next frame is due so sources with their own timing play in real time.
+pub(crate) struct Pacer {
+    next_frame_at: Option<Instant>,
+}
+
+impl Pacer {
+    /// Function that creates a pacer that lets the first frame through immediately.
+    pub(crate) fn new() -> Self {
+        Self {
+            next_frame_at: None,
+        }
+    }
+
+    /// Function that blocks until the current frame is due and schedules the next one `frame_duration` later.
+    pub(crate) fn wait(&mut self, frame_duration: Duration) {
+        let now = Instant::now();
+        let due = *self.next_frame_at.get_or_insert(now);
+
+        if due > now {
+            std::thread::sleep(due - now);
+        }
+
+        // If playback fell behind, restart the schedule instead of rushing through the backlog.
+        let next = due + frame_duration;
+        self.next_frame_at = Some(next.max(Instant::now()));
+    }
+}
+
+/// Function that returns the size, in bytes, of a packed `rgb24` frame. Fails when it is bigger than
+/// `MAX_FRAME_BYTES`.
+pub fn raw_frame_bytes(width: u32, height: u32) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .filter(|&bytes| bytes <= MAX_FRAME_BYTES)
+        .ok_or_else(|| format!("The frame size {width}x{height} is too large.").into())
+}
+
+/// Function that reads one packed `rgb24` frame. Returns `None` when the reader ends before a whole frame.
+pub(crate) fn read_raw_rgb(
+    reader: &mut impl Read,
+    width: u32,
+    height: u32,
+) -> Result<Option<RgbImage>, Box<dyn Error + Send + Sync>> {
+    let mut bytes = vec![0u8; raw_frame_bytes(width, height)?];
+
+    match read_exact_or_eof(reader, &mut bytes)? {
+        true => Ok(ImageBuffer::from_raw(width, height, bytes)),
+        false => Ok(None),
+    }
+}
+
+/// Function that fills the buffer from the reader. Returns false, instead of an error, if the reader ends first.
+pub(crate) fn read_exact_or_eof(
+    reader: &mut impl Read,
+    buffer: &mut [u8],
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    match reader.read_exact(buffer) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Function that converts the planar BT.601 (limited range) YUV planes of a frame into RGB.
+fn yuv_to_rgb(header: &Y4mHeader, planes: &[u8]) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let (width, height) = (header.width, header.height);
+    let (chroma_x, chroma_y) = header.chroma.plane_size(width, height);
+    let luma_size = (width * height) as usize;
+    let chroma_size = (chroma_x * chroma_y) as usize;
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let luma = planes[(y * width + x) as usize] as f32;
+
+        let (u, v) = match header.chroma {
+            Chroma::Mono => (128., 128.),
+            _ => {
+                let i = ((y * chroma_y / height) * chroma_x + x * chroma_x / width) as usize;
+                (
+                    planes[luma_size + i] as f32,
+                    planes[luma_size + chroma_size + i] as f32,
+                )
+            }
+        };
+
+        let c = 1.164 * (luma - 16.);
+        let (d, e) = (u - 128., v - 128.);
+
+        Rgb([
+            (c + 1.596 * e).clamp(0., 255.) as u8,
+            (c - 0.392 * d - 0.813 * e).clamp(0., 255.) as u8,
+            (c + 2.017 * d).clamp(0., 255.) as u8,
+        ])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_raw_frame_sizes() {
+        assert_eq!(raw_frame_bytes(2, 3).unwrap(), 18);
+        assert_eq!(raw_frame_bytes(15360, 8640).unwrap(), MAX_FRAME_BYTES);
+        assert!(raw_frame_bytes(40000, 40000).is_err());
+        assert!(raw_frame_bytes(u32::MAX, u32::MAX).is_err());
+    }
+
+    /// Function that writes a Y4M file to the temporary directory and returns its path.
+    fn write_clip(name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{name}", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn parses_y4m_headers() {
+        assert_eq!(
+            Y4mHeader::parse("YUV4MPEG2 W2 H2 F30000:1001 Ip A1:1 C420jpeg XYSCSS=420JPEG")
+                .unwrap(),
+            Y4mHeader {
+                width: 2,
+                height: 2,
+                fps: 30000. / 1001.,
+                chroma: Chroma::C420,
+            }
+        );
+        assert_eq!(
+            Y4mHeader::parse("YUV4MPEG2 W2 H2 Cmono").unwrap().chroma,
+            Chroma::Mono
+        );
+        assert!(Y4mHeader::parse("YUV4MPEG2 W2 H2 éx").is_ok());
+        assert!(Y4mHeader::parse("YUV4MPEG2 W2 H2 C420p10").is_err());
+        assert!(Y4mHeader::parse("YUV4MPEG2 W2 H2 C444p12").is_err());
+        assert!(Y4mHeader::parse("YUV4MPEG2 H2").is_err());
+        assert!(Y4mHeader::parse("MPEG W2 H2").is_err());
+    }
+
+    #[test]
+    fn converts_yuv_to_rgb() {
+        let header = Y4mHeader::parse("YUV4MPEG2 W2 H2 C420").unwrap();
+        // Black and white luma with neutral chroma, then a saturated red.
+        let grey = yuv_to_rgb(&header, &[16, 235, 235, 16, 128, 128]);
+        let red = yuv_to_rgb(&header, &[81, 81, 81, 81, 90, 240]);
+
+        assert_eq!(grey.get_pixel(0, 0), &Rgb([0, 0, 0]));
+        assert_eq!(grey.get_pixel(1, 0), &Rgb([254, 254, 254]));
+        assert_eq!(grey.get_pixel(0, 1), &Rgb([254, 254, 254]));
+        assert_eq!(grey.get_pixel(1, 1), &Rgb([0, 0, 0]));
+        assert!(
+            red.pixels()
+                .all(|p| p.0[0] > 240 && p.0[1] < 10 && p.0[2] < 10)
+        );
+    }
+
+    #[test]
+    fn plays_a_y4m_clip() {
+        let path = write_clip(
+            "clip.y4m",
+            b"YUV4MPEG2 W2 H2 F1000:1 C420\nFRAME\n\x10\x10\x10\x10\x80\x80FRAME\n\xeb\xeb\xeb\xeb\x80\x80",
+        );
+        let end_flag = Arc::new(AtomicBool::new(false));
+        let mut clip = VideoFile::open_path(&path, None, false, end_flag.clone()).unwrap();
+
+        assert_eq!(clip.fps(), 1000.);
+        assert_eq!(
+            clip.get_frame_rgb().unwrap().get_pixel(0, 0),
+            &Rgb([0, 0, 0])
+        );
+        assert_eq!(
+            clip.get_frame_rgb().unwrap().get_pixel(1, 1),
+            &Rgb([254, 254, 254])
+        );
+        assert!(!end_flag.load(Ordering::Acquire));
+
+        // The last frame is repeated once the clip ends.
+        assert_eq!(
+            clip.get_frame_rgb().unwrap().get_pixel(0, 0),
+            &Rgb([254, 254, 254])
+        );
+        assert!(end_flag.load(Ordering::Acquire));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_oversized_y4m_headers() {
+        assert!(Y4mHeader::parse("YUV4MPEG2 W640 H480 F30:1").is_ok());
+        assert!(Y4mHeader::parse("YUV4MPEG2 W40000 H40000 F30:1").is_err());
+    }
+}
@@ -1,50 +1,148 @@
 //! Module that communicates with the webcam API and converts it into a feed.
 
-use crate::feed::Feed;
-use bincode::config::{self, Configuration};
+use crate::config::FeedConfig;
+use crate::feed::{Feed, FeedConstructor, FeedSource};
 use image::{ImageBuffer, Rgb};
 use nokhwa::{
     Camera, nokhwa_initialize,
     pixel_format::RgbFormat,
     query,
-    utils::{ApiBackend, CameraFormat, RequestedFormat, RequestedFormatType, Resolution},
+    utils::{
+        ApiBackend, CameraFormat, CameraInfo, FrameFormat, RequestedFormat, RequestedFormatType,
+        Resolution,
+    },
 };
-use std::{error::Error, time::Duration};
+use std::error::Error;
+use std::str::FromStr;
+
+/// Enum that represents how a camera is chosen.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub enum CameraSelector {
+    /// The first camera found.
+    #[default]
+    First,
+    /// The camera at the given position of `WebCam::list_cameras`.
+    Index(usize),
+    /// The first camera whose name contains the given text (case-insensitive).
+    Name(String),
+}
+
+impl FromStr for CameraSelector {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    /// Function that parses `first`, the index of a camera or a part of its name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err("The camera selector is empty.".into()),
+            "first" => Ok(Self::First),
+            _ => match s.parse() {
+                Ok(index) => Ok(Self::Index(index)),
+                Err(_) => Ok(Self::Name(s.to_string())),
+            },
+        }
+    }
+}
+
+/// Struct that represents the information of an available camera.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CameraDescription {
+    pub index: usize,
+    pub name: String,
+    pub description: String,
+}
 
 pub struct WebCam {
     pub camera: Camera,
 }
 
-impl Feed for WebCam {
-    const FRAME_RATE: u32 = 600;
-    const ENCODE_CONFIG: Configuration = config::standard();
-    const TIMEOUT_DURATION: Duration = Duration::from_secs(1);
+impl WebCam {
+    /// Function that returns the formats tried, in order, when no preference is given.
+    pub fn default_preferences() -> Vec<CameraFormat> {
+        vec![
+            CameraFormat::new(Resolution::new(640, 480), FrameFormat::MJPEG, 30),
+            CameraFormat::new(Resolution::new(640, 480), FrameFormat::YUYV, 30),
+            CameraFormat::new(Resolution::new(1280, 720), FrameFormat::MJPEG, 30),
+        ]
+    }
+
+    /// Function that lists the cameras available.
+    pub fn list_cameras() -> Result<Vec<CameraDescription>, Box<dyn Error + Send + Sync>> {
+        Ok(query(ApiBackend::Auto)?
+            .iter()
+            .enumerate()
+            .map(|(index, info)| CameraDescription {
+                index,
+                name: info.human_name(),
+                description: info.description().to_string(),
+            })
+            .collect())
+    }
 
-    fn new() -> Result<Self, Box<dyn Error + Send + Sync>> {
+    /// Function that opens the selected camera with the supported format that best matches the preferences.
+    pub fn open(
+        selector: &CameraSelector,
+        preferences: &[CameraFormat],
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
         nokhwa_initialize(|granted| {
             println!("Access granted: {}.", granted);
         });
 
         let cameras = query(ApiBackend::Auto)?;
+        let camera_info = Self::select(&cameras, selector)?;
 
-        let rgb_format =
-            RequestedFormat::new::<RgbFormat>(RequestedFormatType::Exact(CameraFormat::new(
-                Resolution::new(640, 480),
-                nokhwa::utils::FrameFormat::MJPEG,
-                30,
-            )));
+        let mut camera = Camera::new(
+            camera_info.index().clone(),
+            RequestedFormat::new::<RgbFormat>(RequestedFormatType::None),
+        )?;
 
-        let first_camera = match cameras.first() {
-            Some(c) => c,
-            _ => return Err("Couldn't connect to the camera.".into()),
+        let supported = camera.compatible_camera_formats()?;
+        let format = match negotiate_format(preferences, &supported) {
+            Some(format) => format,
+            None => return Err("The camera doesn't report any supported format.".into()),
         };
 
-        let mut threaded = Camera::new(first_camera.index().clone(), rgb_format)?;
-        threaded.open_stream()?;
+        camera.set_camera_requset(RequestedFormat::new::<RgbFormat>(
+            RequestedFormatType::Exact(format),
+        ))?;
+        camera.open_stream()?;
+
+        Ok(Self { camera })
+    }
 
-        Ok(Self { camera: threaded })
+    /// Function that returns the format the camera is capturing with.
+    pub fn format(&self) -> CameraFormat {
+        self.camera.camera_format()
     }
 
+    /// Function that picks a camera using a selector.
+    fn select<'a>(
+        cameras: &'a [CameraInfo],
+        selector: &CameraSelector,
+    ) -> Result<&'a CameraInfo, Box<dyn Error + Send + Sync>> {
+        let camera = match selector {
+            CameraSelector::First => cameras.first(),
+            CameraSelector::Index(i) => cameras.get(*i),
+            CameraSelector::Name(name) => cameras
+                .iter()
+                .find(|c| c.human_name().to_lowercase().contains(&name.to_lowercase())),
+        };
+
+        match camera {
+            Some(c) => Ok(c),
+            _ => Err(format!("Couldn't connect to the camera ({selector:?}).").into()),
+        }
+    }
+}
+
+impl Feed for WebCam {}
+
+impl FeedConstructor for WebCam {
+    fn new(_config: &FeedConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Self::open(&CameraSelector::default(), &Self::default_preferences())
+    }
+}
+
+impl FeedSource for WebCam {
     fn get_frame_rgb(
         &mut self,
     ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, Box<dyn Error + Send + Sync>> {
@@ -54,3 +152,120 @@ impl Feed for WebCam {
         Ok(rgb_image)
     }
 }
+
+/// Function that chooses a format from the ones a camera supports. The first preference that is supported
+/// exactly wins. Otherwise the format closest to the first preference is chosen, comparing the resolution
+/// first, then whether the frame format matches and finally the frame rate.
+pub fn negotiate_format(
+    preferences: &[CameraFormat],
+    supported: &[CameraFormat],
+) -> Option<CameraFormat> {
+    if let Some(format) = preferences.iter().find(|p| supported.contains(p)) {
+        return Some(*format);
+    }
+
+    let preferred = match preferences.first() {
+        Some(preferred) => preferred,
+        None => return supported.first().copied(),
+    };
+
+    supported.iter().copied().min_by_key(|candidate| {
+        let area = |f: &CameraFormat| f.width() as i64 * f.height() as i64;
+
+        (
+            (area(preferred) - area(candidate)).unsigned_abs(),
+            preferred.format() != candidate.format(),
+            (preferred.frame_rate() as i64 - candidate.frame_rate() as i64).unsigned_abs(),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(width: u32, height: u32, frame_format: FrameFormat, fps: u32) -> CameraFormat {
+        CameraFormat::new(Resolution::new(width, height), frame_format, fps)
+    }
+
+    #[test]
+    fn picks_an_exact_match_of_the_first_preference() {
+        let supported = [
+            format(1280, 720, FrameFormat::MJPEG, 30),
+            format(640, 480, FrameFormat::MJPEG, 30),
+        ];
+
+        assert_eq!(
+            negotiate_format(&WebCam::default_preferences(), &supported),
+            Some(format(640, 480, FrameFormat::MJPEG, 30))
+        );
+    }
+
+    #[test]
+    fn picks_a_later_preference_when_the_first_is_not_supported() {
+        let supported = [
+            format(320, 240, FrameFormat::MJPEG, 30),
+            format(1280, 720, FrameFormat::MJPEG, 30),
+            format(640, 480, FrameFormat::YUYV, 30),
+        ];
+
+        assert_eq!(
+            negotiate_format(&WebCam::default_preferences(), &supported),
+            Some(format(640, 480, FrameFormat::YUYV, 30))
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_closest_resolution() {
+        let preferences = [format(640, 480, FrameFormat::MJPEG, 30)];
+        let supported = [
+            format(1920, 1080, FrameFormat::MJPEG, 30),
+            format(800, 600, FrameFormat::YUYV, 5),
+            format(320, 240, FrameFormat::MJPEG, 30),
+        ];
+
+        assert_eq!(
+            negotiate_format(&preferences, &supported),
+            Some(format(800, 600, FrameFormat::YUYV, 5))
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_frame_format_then_the_frame_rate() {
+        let preferences = [format(640, 480, FrameFormat::MJPEG, 30)];
+
+        let supported = [
+            format(640, 480, FrameFormat::YUYV, 30),
+            format(640, 480, FrameFormat::MJPEG, 5),
+        ];
+        assert_eq!(
+            negotiate_format(&preferences, &supported),
+            Some(format(640, 480, FrameFormat::MJPEG, 5))
+        );
+
+        let supported = [
+            format(640, 480, FrameFormat::YUYV, 60),
+            format(640, 480, FrameFormat::YUYV, 25),
+        ];
+        assert_eq!(
+            negotiate_format(&preferences, &supported),
+            Some(format(640, 480, FrameFormat::YUYV, 25))
+        );
+    }
+
+    #[test]
+    fn picks_the_first_supported_format_without_preferences() {
+        let supported = [
+            format(1280, 720, FrameFormat::YUYV, 10),
+            format(640, 480, FrameFormat::MJPEG, 30),
+        ];
+
+        assert_eq!(negotiate_format(&[], &supported), Some(supported[0]));
+    }
+
+    #[test]
+    fn returns_none_without_supported_formats() {
+        assert_eq!(negotiate_format(&WebCam::default_preferences(), &[]), None);
+        assert_eq!(negotiate_format(&[], &[]), None);
+    }
+}
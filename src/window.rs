@@ -3,8 +3,10 @@
 use termcolor::{BufferWriter, ColorChoice};
 use tokio::net::UdpSocket;
 
-use crate::feed::Feed;
-use crate::feed::frame::AsciiEncoding;
+use crate::config::FeedConfig;
+use crate::feed::{Feed, FeedConstructor};
+use crate::render::Renderer;
+use crate::switch::SourceSwitch;
 use std::error::Error;
 use std::sync::{Arc, atomic::AtomicBool};
 
@@ -26,30 +28,68 @@ impl Window {
     }
 
     /// Function that displays the feed from any source in the colored stdout.
-    pub async fn show_feed<T: Feed + Send>(
+    pub async fn show_feed<T: FeedConstructor + Send>(
         self,
-        encoding: AsciiEncoding,
+        renderer: Renderer,
+        config: FeedConfig,
         end_flag: Arc<AtomicBool>,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        T::show(self.buffer_writer, encoding, end_flag).await
+        T::show(self.buffer_writer, renderer, config, end_flag).await
+    }
+
+    /// Function that displays the feed from an already created source in the colored stdout.
+    pub async fn show_feed_from<T: Feed + Send>(
+        self,
+        feed_source: T,
+        renderer: Renderer,
+        config: FeedConfig,
+        end_flag: Arc<AtomicBool>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        T::show_from(feed_source, self.buffer_writer, renderer, config, end_flag).await
     }
 
     /// Function that streams the feed captured from any feed source.
-    pub async fn stream_feed<T: Feed + Send>(
+    pub async fn stream_feed<T: FeedConstructor + Send>(
+        self,
+        connection: UdpSocket,
+        config: FeedConfig,
+        end_flag: Arc<AtomicBool>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        T::stream(connection, config, end_flag).await
+    }
+
+    /// Function that streams the feed captured from an already created source.
+    pub async fn stream_feed_from<T: Feed + Send>(
+        self,
+        feed_source: T,
+        connection: UdpSocket,
+        config: FeedConfig,
+        end_flag: Arc<AtomicBool>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        T::stream_from(feed_source, connection, config, end_flag).await
+    }
+
+    /// Function that streams the feed captured from an already created source, moving to any source sent through
+    /// the switch without dropping the connection.
+    pub async fn stream_feed_switchable<T: Feed + Send>(
         self,
+        feed_source: T,
+        switch: SourceSwitch<T>,
         connection: UdpSocket,
+        config: FeedConfig,
         end_flag: Arc<AtomicBool>,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        T::stream(connection, end_flag).await
+        T::stream_switchable(feed_source, switch, connection, config, end_flag).await
     }
 
     /// Function that shows the feed received from an UDP socket connection.
     pub async fn show_stream_feed<T: Feed + Send>(
         self,
         connection: UdpSocket,
-        encoding: AsciiEncoding,
+        renderer: Renderer,
+        config: FeedConfig,
         end_flag: Arc<AtomicBool>,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        T::show_stream(self.buffer_writer, connection, &encoding, end_flag).await
+        T::show_stream(self.buffer_writer, connection, renderer, config, end_flag).await
     }
 }
@@ -0,0 +1,199 @@
+//! Module that communicates with the window capturing API and converts a single application window into a feed.
+
+use crate::{
+    FILTER,
+    config::FeedConfig,
+    feed::{Feed, FeedSource},
+    screen_capture::{MAX_CAPTURE_SIZE, fit_size},
+};
+use image::{DynamicImage, ImageBuffer, Rgb};
+use std::error::Error;
+use std::str::FromStr;
+use xcap::Window;
+
+/// Enum that represents how the shared window is chosen. Matching is case-insensitive and by substring.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WindowSelector {
+    Title(String),
+    AppName(String),
+}
+
+impl WindowSelector {
+    /// Function that checks if a window matches the selector.
+    fn matches(&self, window: &Window) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let (value, pattern) = match self {
+            Self::Title(title) => (window.title()?, title),
+            Self::AppName(app_name) => (window.app_name()?, app_name),
+        };
+
+        Ok(value.to_lowercase().contains(&pattern.to_lowercase()))
+    }
+}
+
+impl FromStr for WindowSelector {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    /// Function that parses `app=NAME` to match the application name, or `title=TITLE` (or just `TITLE`) to match
+    /// the title.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let selector = match s.split_once('=') {
+            Some(("app", app_name)) => Self::AppName(app_name.to_string()),
+            Some(("title", title)) => Self::Title(title.to_string()),
+            _ => Self::Title(s.to_string()),
+        };
+
+        match selector {
+            Self::Title(pattern) | Self::AppName(pattern) if pattern.is_empty() => {
+                Err("The window selector is empty.".into())
+            }
+            selector => Ok(selector),
+        }
+    }
+}
+
+/// Struct that represents the information of a window that can be shared.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WindowInfo {
+    pub id: u32,
+    pub title: String,
+    pub app_name: String,
+}
+
+/// Struct that represents a single application window being shared. The window is looked up again on every
+/// frame so moves and resizes are followed, and a placeholder is shown while it is closed or minimized.
+pub struct WindowCapture {
+    pub selector: WindowSelector,
+    window_id: Option<u32>,
+}
+
+impl WindowCapture {
+    /// Color of the frame shown while the shared window is unavailable.
+    const PLACEHOLDER_COLOR: Rgb<u8> = Rgb([32, 32, 32]);
+
+    /// Function that lists the windows available to be shared.
+    pub fn list_windows() -> Result<Vec<WindowInfo>, Box<dyn Error + Send + Sync>> {
+        Window::all()?
+            .iter()
+            .map(|window| {
+                Ok(WindowInfo {
+                    id: window.id()?,
+                    title: window.title()?,
+                    app_name: window.app_name()?,
+                })
+            })
+            .collect()
+    }
+
+    /// Function that creates a window feed from the first window that matches the selector.
+    pub fn with_selector(selector: WindowSelector) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let window = match Self::find(&selector)? {
+            Some(window) => window,
+            None => return Err(format!("There is no window matching {selector:?}.").into()),
+        };
+
+        Ok(Self {
+            window_id: Some(window.id()?),
+            selector,
+        })
+    }
+
+    /// Function that returns the first window that matches the selector.
+    fn find(selector: &WindowSelector) -> Result<Option<Window>, Box<dyn Error + Send + Sync>> {
+        for window in Window::all()? {
+            if selector.matches(&window)? {
+                return Ok(Some(window));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Function that returns the shared window with up to date geometry. If it was closed, another window
+    /// matching the selector is picked up.
+    fn current_window(&mut self) -> Result<Option<Window>, Box<dyn Error + Send + Sync>> {
+        let windows = Window::all()?;
+
+        if let Some(id) = self.window_id {
+            for window in windows {
+                if window.id()? == id {
+                    return Ok(Some(window));
+                }
+            }
+        }
+
+        let window = Self::find(&self.selector)?;
+        self.window_id = match &window {
+            Some(window) => Some(window.id()?),
+            None => None,
+        };
+
+        Ok(window)
+    }
+
+    /// Function that creates the frame shown while the shared window is unavailable.
+    fn placeholder() -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        let (width, height) = MAX_CAPTURE_SIZE;
+        ImageBuffer::from_pixel(width, height, Self::PLACEHOLDER_COLOR)
+    }
+}
+
+impl Feed for WindowCapture {
+    fn default_config() -> FeedConfig {
+        FeedConfig {
+            frame_rate: 200,
+            ..FeedConfig::default()
+        }
+    }
+}
+
+impl FeedSource for WindowCapture {
+    fn get_frame_rgb(
+        &mut self,
+    ) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, Box<dyn Error + Send + Sync>> {
+        let window = match self.current_window()? {
+            Some(window) if !window.is_minimized()? => window,
+            _ => return Ok(Self::placeholder()),
+        };
+
+        // The window can disappear between being listed and being captured.
+        let frame = match window.capture_image() {
+            Ok(frame) => frame,
+            Err(_) => return Ok(Self::placeholder()),
+        };
+
+        let (width, height) = fit_size(frame.dimensions(), MAX_CAPTURE_SIZE);
+        let frame = image::imageops::resize(&frame, width, height, FILTER);
+
+        Ok(DynamicImage::ImageRgba8(frame).into_rgb8())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_window_selectors() {
+        assert_eq!(
+            "Firefox".parse::<WindowSelector>().unwrap(),
+            WindowSelector::Title("Firefox".to_string())
+        );
+        assert_eq!(
+            "app=firefox".parse::<WindowSelector>().unwrap(),
+            WindowSelector::AppName("firefox".to_string())
+        );
+        assert_eq!(
+            "title=a=b".parse::<WindowSelector>().unwrap(),
+            WindowSelector::Title("a=b".to_string())
+        );
+        // Titles that only look like an option are still titles.
+        assert_eq!(
+            "x=1".parse::<WindowSelector>().unwrap(),
+            WindowSelector::Title("x=1".to_string())
+        );
+
+        ["", "app=", "title="].iter().for_each(|selector| {
+            assert!(selector.parse::<WindowSelector>().is_err(), "{selector}")
+        });
+    }
+}